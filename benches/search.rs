@@ -0,0 +1,199 @@
+//! How searching scales with the number of notes.
+//!
+//! Corpora of synthetic notes are generated once into
+//! `target/bench-fixtures/` and reused. Set `CHALSEDONY_BENCH_SIZES` to a
+//! comma separated list of note counts to override the default sizes, e.g.
+//! `CHALSEDONY_BENCH_SIZES=1000 cargo bench`.
+//!
+//! Typing benchmarks replay every prefix of a query, as the picker sees it
+//! while a word is typed, and report keystrokes per second.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use egui_chalsedony_note_selector::bm25::{bm25_scores, FieldWeights, NoteIndex};
+use egui_chalsedony_note_selector::fixture::Fixture;
+use egui_chalsedony_note_selector::history::History;
+use egui_chalsedony_note_selector::note::{Include, Note};
+use egui_chalsedony_note_selector::open::Opener;
+use egui_chalsedony_note_selector::pins::Pins;
+use egui_chalsedony_note_selector::query::Query;
+use egui_chalsedony_note_selector::tokenizer::{NGrams, TokenizerConfig, TokenizerKind};
+use egui_chalsedony_note_selector::ui::{MyApp, UiOptions};
+use std::hint::black_box;
+use std::path::PathBuf;
+
+const DEFAULT_SIZES: &[usize] = &[10_000, 50_000, 100_000];
+
+/// A title search typed one key at a time
+const TITLE_KEYSTROKES: &[&str] = &["g", "ga", "gar", "gard", "garde", "garden"];
+
+/// A body search typed one key at a time
+const BODY_KEYSTROKES: &[&str] = &["b:g", "b:ga", "b:gar", "b:gard", "b:garde", "b:garden"];
+
+fn sizes() -> Vec<usize> {
+    match std::env::var("CHALSEDONY_BENCH_SIZES") {
+        Ok(sizes) => sizes
+            .split(',')
+            .map(|size| size.trim().parse().expect("invalid CHALSEDONY_BENCH_SIZES"))
+            .collect(),
+        Err(_) => DEFAULT_SIZES.to_vec(),
+    }
+}
+
+/// Path of a generated database of `size` notes, generating it if missing
+fn corpus(size: usize) -> String {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/bench-fixtures");
+    let path = dir.join(format!("notes-{size}.sqlite"));
+    if !path.exists() {
+        std::fs::create_dir_all(&dir).expect("unable to create fixture directory");
+        // Generate next to the final path so an interrupted run is not reused
+        let partial = dir.join(format!("notes-{size}.sqlite.partial"));
+        let _ = std::fs::remove_file(&partial);
+        Fixture::create(&partial, 0)
+            .and_then(|mut fixture| fixture.generate(size))
+            .expect("unable to generate fixture database");
+        std::fs::rename(&partial, &path).expect("unable to move fixture database");
+    }
+    path.to_str()
+        .expect("fixture path is not UTF-8")
+        .to_string()
+}
+
+fn load(path: &str) -> Vec<Note> {
+    Note::load_all(path, Include::default()).expect("unable to load fixture database")
+}
+
+fn tokenize(c: &mut Criterion) {
+    let notes = load(&corpus(sizes()[0]));
+    let body = &notes
+        .iter()
+        .max_by_key(|note| note.body.len())
+        .expect("fixture has notes")
+        .body;
+    let mut group = c.benchmark_group("tokenize");
+    group.throughput(Throughput::Bytes(body.len() as u64));
+    for kind in [
+        TokenizerKind::Ngrams,
+        TokenizerKind::Words,
+        TokenizerKind::Stemmed,
+    ] {
+        let tokenizer = TokenizerConfig {
+            kind,
+            ..TokenizerConfig::default()
+        }
+        .build();
+        group.bench_function(format!("{kind:?} longest body"), |b| {
+            b.iter(|| {
+                tokenizer.tokenize(black_box(body), &mut |term| {
+                    black_box(term);
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+fn ranking(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bm25");
+    group.sample_size(10);
+    for (i, size) in sizes().into_iter().enumerate() {
+        let notes = load(&corpus(size));
+        // The unindexed scorer takes seconds a query, so only the smallest corpus
+        if i == 0 {
+            let documents: Vec<String> = notes
+                .iter()
+                .map(|note| format!("{} {}", note.title, note.body))
+                .collect();
+            group.bench_with_input(
+                BenchmarkId::new("bm25_scores", size),
+                &documents,
+                |b, documents| {
+                    b.iter(|| bm25_scores(documents, black_box("garden"), &NGrams::trigrams()))
+                },
+            );
+        }
+        group.bench_with_input(
+            BenchmarkId::new("NoteIndex::new", size),
+            &notes,
+            |b, notes| b.iter(|| NoteIndex::new(notes, TokenizerConfig::default().build())),
+        );
+
+        let index = NoteIndex::new(&notes, TokenizerConfig::default().build());
+        group.throughput(Throughput::Elements(TITLE_KEYSTROKES.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::new("NoteIndex::scores", size),
+            &index,
+            |b, index| {
+                b.iter(|| {
+                    for query in TITLE_KEYSTROKES {
+                        black_box(index.scores(query, FieldWeights::default()));
+                    }
+                })
+            },
+        );
+        group.throughput(Throughput::Elements(1));
+    }
+    group.finish();
+}
+
+fn find(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Note::find");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(TITLE_KEYSTROKES.len() as u64));
+    for size in sizes() {
+        let path = corpus(size);
+        for (name, keystrokes) in [("title", TITLE_KEYSTROKES), ("body", BODY_KEYSTROKES)] {
+            let queries: Vec<Query> = keystrokes
+                .iter()
+                .map(|query| Query::parse(query).expect("benchmark query is valid"))
+                .collect();
+            group.bench_with_input(BenchmarkId::new(name, size), &queries, |b, queries| {
+                b.iter(|| {
+                    for query in queries {
+                        black_box(Note::find(&path, query, Include::default()).unwrap());
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn update_filtered_notes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update_filtered_notes");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(TITLE_KEYSTROKES.len() as u64));
+    for size in sizes() {
+        let options = UiOptions {
+            opener: Opener::new(None, None),
+            allow_write: false,
+            include: Include::default(),
+            export_dir: std::env::temp_dir(),
+            notebook: None,
+            history: History::default(),
+            pins: Pins::default(),
+            weights: FieldWeights::default(),
+            tokenizer: TokenizerConfig::default(),
+            fresh: false,
+            start: None,
+            select_1: false,
+            exit_0: false,
+        };
+        let mut app = MyApp::new(corpus(size), options);
+        // The index is built on the first keystroke and kept, as in the picker
+        app.set_query("g");
+        for (name, keystrokes) in [("title", TITLE_KEYSTROKES), ("body", BODY_KEYSTROKES)] {
+            group.bench_function(BenchmarkId::new(name, size), |b| {
+                b.iter(|| {
+                    for query in keystrokes {
+                        app.set_query(query);
+                        black_box(app.notes());
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tokenize, ranking, find, update_filtered_notes);
+criterion_main!(benches);
@@ -1,49 +1,22 @@
-use crate::note::Note;
-use ordered_float::OrderedFloat;
+use crate::note::{Include, Note};
+use crate::tokenizer::{Tokenizer, Words};
+use crate::typo::Vocabulary;
+use rusqlite::Result as SqlResult;
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
-fn default_tokenize(s: &str) -> Vec<String> {
-    s.split_whitespace().map(String::from).collect()
-}
-
-pub fn bm25_trigram(documents: &[String], target_string: &str) -> Vec<String> {
-    bm25(documents, target_string, ngram_tokenize)
-}
-
-/// Sort notes by relevance to query using BM25 with trigram tokenization
-#[allow(dead_code)]
-fn sort_notes_trigram(notes: &[Note], query: &str) -> Vec<Note> {
-    // Extract document strings from notes
-    let documents: Vec<String> = notes
-        .iter()
-        .map(|note| format!("{} {}", note.title, note.body))
-        .collect();
-
-    // Get sorted indices using BM25 trigram
-    let sorted_docs = bm25_trigram(&documents, query);
-
-    // Map sorted documents back to original notes
-    sorted_docs
-        .into_iter()
-        .filter_map(|doc| {
-            notes
-                .iter()
-                .find(|note| format!("{} {}", note.title, note.body) == doc)
-                .cloned()
-        })
-        .collect()
-}
-
-pub fn bm25(
+/// BM25 score of each document against the target, in document order
+pub fn bm25_scores(
     documents: &[String],
     target_string: &str,
-    tokenize: fn(&str) -> Vec<String>,
-) -> Vec<String> {
+    tokenizer: &dyn Tokenizer,
+) -> Vec<f64> {
     // Tokenize documents
-    let tokenized_docs: Vec<Vec<String>> = documents.iter().map(|doc| tokenize(doc)).collect();
+    let tokenized_docs: Vec<Vec<String>> =
+        documents.iter().map(|doc| tokenizer.terms(doc)).collect();
 
     // Process query terms
-    let query_terms = tokenize(target_string);
+    let query_terms = tokenizer.terms(target_string);
     let term_frequencies_query: HashMap<String, usize> = {
         let unique_terms: HashSet<_> = query_terms.iter().cloned().collect();
         unique_terms
@@ -70,9 +43,9 @@ pub fn bm25(
     let b = 0.75;
 
     // Calculate scores
-    let mut bm25_scores: HashMap<usize, f64> = HashMap::new();
+    let mut bm25_scores = Vec::with_capacity(tokenized_docs.len());
 
-    for (i, doc) in tokenized_docs.iter().enumerate() {
+    for doc in tokenized_docs.iter() {
         let term_frequencies_doc: HashMap<String, usize> = {
             let unique_terms: HashSet<_> = doc.iter().cloned().collect();
             unique_terms
@@ -95,52 +68,209 @@ pub fn bm25(
             }
         }
 
-        bm25_scores.insert(i, score);
+        bm25_scores.push(score);
     }
 
-    // Sort by scores
-    let mut indices: Vec<usize> = (0..documents.len()).collect();
-    indices.sort_by_key(|&i| OrderedFloat(-bm25_scores.get(&i).unwrap_or(&0.0)));
-
-    // Return sorted documents
-    indices.into_iter().map(|i| documents[i].clone()).collect()
+    bm25_scores
 }
 
-fn ngram_tokenize(s: &str) -> Vec<String> {
-    const N: usize = 3; // trigrams
+/// Weight of each note field when scoring with a [`NoteIndex`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldWeights {
+    pub title: f64,
+    pub body: f64,
+}
 
-    // Handle edge cases
-    if s.len() < N {
-        return vec![s.to_string()];
+impl Default for FieldWeights {
+    fn default() -> Self {
+        Self {
+            title: 3.0,
+            body: 1.0,
+        }
     }
+}
 
-    // Convert to lowercase to make matching case-insensitive
-    let text = s.to_lowercase();
+/// Only the start of long bodies is indexed, it keeps the index small and
+/// is where what a note is about is usually written
+const BODY_INDEX_CHARS: usize = 4_000;
 
-    // Create sliding window of N characters
-    text.chars()
-        .collect::<Vec<char>>()
-        .windows(N)
-        .map(|window| window.iter().collect::<String>())
-        .collect()
+const TITLE: usize = 0;
+const BODY: usize = 1;
+
+/// Occurrences of a term in the title and body of one note
+#[derive(Debug)]
+struct Posting {
+    doc: u32,
+    term_frequencies: [u32; 2],
 }
 
-#[allow(dead_code)]
-fn main() {
-    let documents = vec![
-        "the quick brown fox jumps over the lazy dog".to_string(),
-        "never jump over the lazy dog quickly".to_string(),
-        "a quick movement of the enemy will jeopardize six gunboats".to_string(),
-        "quick and nimble cats jump over sleeping dogs".to_string(),
-    ];
-    let target_string = "never";
-    let sorted_documents = bm25(&documents, target_string, default_tokenize);
-    println!("{:?}", sorted_documents);
+/// Index of the terms in the titles and bodies of notes, as split by its
+/// [`Tokenizer`], scored with BM25F.
+///
+/// The weighted, length normalised frequencies of a term in each field are
+/// summed before saturation, so a term found in both fields is not counted
+/// twice the way adding separate title and body BM25 scores would. Built once,
+/// queries only touch the postings of their own terms.
+#[derive(Debug)]
+pub struct NoteIndex {
+    postings: HashMap<String, Vec<Posting>>,
+    /// Tokens in the title and body of each note
+    lengths: Vec<[u32; 2]>,
+    average_lengths: [f64; 2],
+    ids: Vec<String>,
+    tokenizer: Arc<dyn Tokenizer>,
+    /// Splits titles and queries into the words typos are corrected between,
+    /// normalised the way the tokenizer normalises terms
+    words: Words,
+    /// Words of the titles, which mistyped query words are corrected to
+    titles: Vocabulary,
+}
+
+impl NoteIndex {
+    pub fn new(notes: &[Note], tokenizer: Arc<dyn Tokenizer>) -> Self {
+        let mut postings: HashMap<String, Vec<Posting>> = HashMap::new();
+        let mut lengths = Vec::with_capacity(notes.len());
+        let mut title_words: HashMap<String, u32> = HashMap::new();
+        let words = Words {
+            normalization: tokenizer.normalization(),
+        };
+
+        for (doc, note) in notes.iter().enumerate() {
+            let mut title: HashSet<String> = HashSet::new();
+            words.tokenize(&note.title, &mut |word| {
+                title.insert(word.to_string());
+            });
+            for word in title {
+                *title_words.entry(word).or_default() += 1;
+            }
+
+            let body = match note.body.char_indices().nth(BODY_INDEX_CHARS) {
+                Some((end, _)) => &note.body[..end],
+                None => &note.body,
+            };
+            let mut term_frequencies: HashMap<String, [u32; 2]> = HashMap::new();
+            let mut length = [0; 2];
+            for (field, text) in [(TITLE, note.title.as_str()), (BODY, body)] {
+                if text.is_empty() {
+                    continue;
+                }
+                tokenizer.tokenize(text, &mut |term| {
+                    match term_frequencies.get_mut(term) {
+                        Some(frequencies) => frequencies[field] += 1,
+                        None => {
+                            let mut frequencies = [0; 2];
+                            frequencies[field] = 1;
+                            term_frequencies.insert(term.to_string(), frequencies);
+                        }
+                    }
+                    length[field] += 1;
+                });
+            }
+            for (term, term_frequencies) in term_frequencies {
+                postings.entry(term).or_default().push(Posting {
+                    doc: doc as u32,
+                    term_frequencies,
+                });
+            }
+            lengths.push(length);
+        }
+
+        let average = |field: usize| {
+            let total: u64 = lengths.iter().map(|length| length[field] as u64).sum();
+            total as f64 / lengths.len().max(1) as f64
+        };
+        let average_lengths = [average(TITLE), average(BODY)];
+
+        Self {
+            postings,
+            lengths,
+            average_lengths,
+            ids: notes.iter().map(|note| note.id.clone()).collect(),
+            tokenizer,
+            words,
+            titles: Vocabulary::new(title_words),
+        }
+    }
+
+    /// Index every note in the database that `include` lets through.
+    ///
+    /// Term statistics come from the whole collection rather than the notes a
+    /// query matched, so the picker and the `search` command rank alike.
+    pub fn load(db_path: &str, include: Include, tokenizer: Arc<dyn Tokenizer>) -> SqlResult<Self> {
+        Ok(Self::new(&Note::load_all(db_path, include)?, tokenizer))
+    }
+
+    /// BM25F score of every note matching any term of the query, by note id.
+    ///
+    /// Query words not in any title are also scored as the closest title
+    /// words, discounted by how many edits apart they are, so a typo still
+    /// finds the note it was meant for.
+    pub fn scores(&self, query: &str, weights: FieldWeights) -> HashMap<String, f64> {
+        let mut scores = self.term_scores(query, weights);
+        for word in self.words.terms(query) {
+            // Best corrected score of each note, corrections of one word are
+            // alternatives rather than adding up
+            let mut corrected: HashMap<String, f64> = HashMap::new();
+            for (correction, distance) in self.titles.corrections(&word) {
+                let discount = 1.0 / (1.0 + distance as f64);
+                for (id, score) in self.term_scores(correction, weights) {
+                    let best = corrected.entry(id).or_default();
+                    *best = best.max(discount * score);
+                }
+            }
+            for (id, score) in corrected {
+                *scores.entry(id).or_default() += score;
+            }
+        }
+        scores
+    }
+
+    /// BM25F score of every note matching any term of the query as written
+    pub(crate) fn term_scores(&self, query: &str, weights: FieldWeights) -> HashMap<String, f64> {
+        let k1 = 1.5;
+        let b = 0.75;
+        let weights = [weights.title, weights.body];
+        let total_docs = self.ids.len() as f64;
+
+        let query_terms: HashSet<String> = self.tokenizer.terms(query).into_iter().collect();
+        let mut scores: HashMap<String, f64> = HashMap::new();
+        for term in query_terms {
+            let Some(postings) = self.postings.get(&term) else {
+                continue;
+            };
+            let docs_with_term = postings.len() as f64;
+            let idf = (1.0 + (total_docs - docs_with_term + 0.5) / (docs_with_term + 0.5)).ln();
+
+            for posting in postings {
+                let length = &self.lengths[posting.doc as usize];
+                let tf: f64 = (0..2)
+                    .map(|field| {
+                        let relative_length = if self.average_lengths[field] > 0.0 {
+                            length[field] as f64 / self.average_lengths[field]
+                        } else {
+                            1.0
+                        };
+                        weights[field] * posting.term_frequencies[field] as f64
+                            / (1.0 - b + b * relative_length)
+                    })
+                    .sum();
+                if tf > 0.0 {
+                    *scores
+                        .entry(self.ids[posting.doc as usize].clone())
+                        .or_default() += idf * tf * (k1 + 1.0) / (tf + k1);
+                }
+            }
+        }
+        scores
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fixture::Fixture;
+    use crate::tokenizer::{NGrams, Normalization, TokenizerConfig, TokenizerKind, Whitespace};
+    use ordered_float::OrderedFloat;
 
     #[test]
     fn test_exact_match() {
@@ -149,9 +279,9 @@ mod tests {
             "never give up".to_string(),
             "quick brown".to_string(),
         ];
-        let target = "never";
-        let result = bm25(&documents, target, default_tokenize);
-        assert_eq!(result[0], "never give up");
+        let scores = bm25_scores(&documents, "never", &Whitespace);
+        assert!(scores[1] > 0.0);
+        assert_eq!((scores[0], scores[2]), (0.0, 0.0));
     }
 
     #[test]
@@ -160,9 +290,8 @@ mod tests {
             "the quick brown fox".to_string(),
             "never give up".to_string(),
         ];
-        let target = "";
-        let result = bm25(&documents, target, default_tokenize);
-        assert_eq!(result.len(), 2);
+        let scores = bm25_scores(&documents, "", &Whitespace);
+        assert_eq!(scores, vec![0.0, 0.0]);
     }
 
     #[test]
@@ -171,10 +300,9 @@ mod tests {
             "the quick brown fox".to_string(),
             "never give up".to_string(),
         ];
-        let target = "zzzzz";
-        let result = bm25(&documents, target, default_tokenize);
-        assert_eq!(result.len(), 2);
-        // Documents should still be returned, just with zero scores
+        // Every document is still scored, just with zero
+        let scores = bm25_scores(&documents, "zzzzz", &Whitespace);
+        assert_eq!(scores, vec![0.0, 0.0]);
     }
 
     #[test]
@@ -183,10 +311,15 @@ mod tests {
             "the-quick-brown-fox".to_string(),
             "never-give-up".to_string(),
         ];
-        let custom_tokenize = |s: &str| s.split('-').map(String::from).collect();
-        let target = "never";
-        let result = bm25(&documents, target, custom_tokenize);
-        assert_eq!(result[0], "never-give-up");
+        #[derive(Debug)]
+        struct Dashes;
+        impl Tokenizer for Dashes {
+            fn tokenize(&self, text: &str, term: &mut dyn FnMut(&str)) {
+                text.split('-').for_each(term);
+            }
+        }
+        let scores = bm25_scores(&documents, "never", &Dashes);
+        assert!(scores[1] > scores[0]);
     }
 
     #[test]
@@ -199,34 +332,179 @@ mod tests {
 
         // This will test trigrams (n=3)
         // For example, "hello" should become: ["hel", "ell", "llo"]
-        let target = "hell";
-        let result = bm25(&documents, target, ngram_tokenize);
+        let scores = bm25_scores(&documents, "hell", &NGrams::trigrams());
 
-        // "hello world" and "help me" should be ranked higher than "world peace"
+        // "hello world" and "help me" should score higher than "world peace"
         // as they both contain "hel" trigrams
-        assert_eq!(result[0], "hello world");
-        assert_eq!(result[1], "help me");
-        assert_eq!(result[2], "world peace");
+        assert!(scores[0] > scores[1]);
+        assert!(scores[1] > scores[2]);
+    }
+
+    fn note(id: &str, title: &str, body: &str) -> Note {
+        Note {
+            body: body.to_string(),
+            ..Note::for_test(id, title)
+        }
+    }
+
+    fn best(scores: &HashMap<String, f64>) -> &str {
+        scores
+            .iter()
+            .max_by_key(|(_, &score)| OrderedFloat(score))
+            .map(|(id, _)| id.as_str())
+            .unwrap()
     }
 
     #[test]
-    fn test_ngram_tokenize_basic() {
-        let result = ngram_tokenize("hello");
-        let expected = vec!["hel", "ell", "llo"];
-        assert_eq!(result, expected);
+    fn test_note_index_weights_fields() {
+        let notes = vec![
+            note("in_title", "Gardening", "tomatoes and beans"),
+            note("in_body", "Weekend", "some gardening and more gardening"),
+            note("neither", "Recipes", "soup"),
+        ];
+        let index = NoteIndex::new(&notes, TokenizerConfig::default().build());
+
+        let scores = index.scores("gardening", FieldWeights::default());
+        assert_eq!(best(&scores), "in_title");
+        assert!(!scores.contains_key("neither"));
+
+        let body_first = FieldWeights {
+            title: 1.0,
+            body: 3.0,
+        };
+        assert_eq!(best(&index.scores("gardening", body_first)), "in_body");
+
+        let titles_only = FieldWeights {
+            title: 1.0,
+            body: 0.0,
+        };
+        let scores = index.scores("gardening", titles_only);
+        assert!(!scores.contains_key("in_body"));
     }
 
     #[test]
-    fn test_ngram_tokenize_short_input() {
-        let result = ngram_tokenize("hi");
-        let expected = vec!["hi"];
-        assert_eq!(result, expected);
+    fn test_note_index_combines_fields() {
+        let notes = vec![
+            note("both", "Tomato notes", "tomato sauce"),
+            note("title", "Tomato notes", "beans"),
+            note("empty", "", ""),
+        ];
+        let index = NoteIndex::new(&notes, TokenizerConfig::default().build());
+        let scores = index.scores("tomato", FieldWeights::default());
+        assert!(scores["both"] > scores["title"]);
+        assert!(index.scores("", FieldWeights::default()).is_empty());
+    }
+
+    #[test]
+    fn test_note_index_ignores_accents() {
+        let notes = vec![
+            note("cafe", "Café opening hours", ""),
+            note("other", "Bakery", ""),
+        ];
+        let index = NoteIndex::new(&notes, TokenizerConfig::default().build());
+        assert_eq!(best(&index.scores("cafe", FieldWeights::default())), "cafe");
+
+        let keep = TokenizerConfig {
+            normalization: Normalization {
+                strip_diacritics: false,
+            },
+            ..TokenizerConfig::default()
+        };
+        let index = NoteIndex::new(&notes, keep.build());
+        assert!(index.scores("cafe", FieldWeights::default()).len() <= 1);
+        assert_eq!(best(&index.scores("café", FieldWeights::default())), "cafe");
+    }
+
+    #[test]
+    fn test_note_index_typos() {
+        let notes = vec![
+            note("meeting", "Meeting notes", ""),
+            note("greeting", "Greeting cards", ""),
+            note("meat", "Meat recipes", "Marinades"),
+            note("garden", "Garden plan", "Tomatoes, beans"),
+            note("recipes", "Recipes", "Bread"),
+        ];
+        let index = NoteIndex::new(&notes, TokenizerConfig::default().build());
+        for (query, expected) in [
+            ("meeitng", "meeting"),    // swapped letters
+            ("meetng", "meeting"),     // missing letter
+            ("meetiing", "meeting"),   // extra letter
+            ("meetimg", "meeting"),    // wrong letter
+            ("gadren plan", "garden"), // swap in one of several words
+            ("reicpes", "recipes"),    // shortest title wins the tie
+            ("grreting", "greeting"),  // two edits
+        ] {
+            assert_eq!(
+                best(&index.scores(query, FieldWeights::default())),
+                expected,
+                "{query}"
+            );
+        }
+        // A typo never outranks the correctly spelled word
+        let scores = index.scores("meeting", FieldWeights::default());
+        let typo = index.scores("meeitng", FieldWeights::default());
+        assert!(typo["meeting"] < scores["meeting"]);
+    }
+
+    #[test]
+    fn test_note_index_typos_keep_diacritics() {
+        let notes = vec![
+            note("resume", "Résumé tips", ""),
+            note("other", "Bakery", ""),
+        ];
+        let keep = TokenizerConfig {
+            kind: TokenizerKind::Words,
+            normalization: Normalization {
+                strip_diacritics: false,
+            },
+            ..TokenizerConfig::default()
+        };
+        let index = NoteIndex::new(&notes, keep.build());
+        // Corrected to the title word as the index holds it, accents included
+        assert_eq!(
+            best(&index.scores("résumw", FieldWeights::default())),
+            "resume"
+        );
+    }
+
+    #[test]
+    fn test_note_index_cjk() {
+        let notes = vec![
+            note("tokyo", "東京都の天気", ""),
+            note("kyoto", "京都旅行", "紅葉"),
+        ];
+        let index = NoteIndex::new(&notes, TokenizerConfig::default().build());
+        assert_eq!(
+            best(&index.scores("京都", FieldWeights::default())),
+            "kyoto"
+        );
+        assert_eq!(
+            best(&index.scores("東京", FieldWeights::default())),
+            "tokyo"
+        );
+        assert_eq!(
+            best(&index.scores("紅葉", FieldWeights::default())),
+            "kyoto"
+        );
     }
 
     #[test]
-    fn test_ngram_tokenize_case_insensitive() {
-        let result = ngram_tokenize("Hello");
-        let expected = vec!["hel", "ell", "llo"];
-        assert_eq!(result, expected);
+    fn test_load_indexes_every_note() {
+        let mut fixture = Fixture::temporary().unwrap();
+        let folder = fixture.folder("Home", "").unwrap();
+        fixture.note("Garden plan", "Tomatoes", &folder).unwrap();
+        fixture.note("Garden tools", "Spade", &folder).unwrap();
+        fixture.note("Meeting notes", "Agenda", &folder).unwrap();
+
+        let index = NoteIndex::load(
+            fixture.path(),
+            Include::default(),
+            TokenizerConfig::default().build(),
+        )
+        .unwrap();
+        assert_eq!(index.ids.len(), 3);
+        // Every note a query term is in, not only ones a filter let through
+        let scores = index.scores("garden", FieldWeights::default());
+        assert_eq!(scores.len(), 2);
     }
 }
@@ -1,4 +1,9 @@
-use clap::{Parser, Subcommand};
+use crate::bm25::FieldWeights;
+use crate::dates::{DateField, DateRange};
+use crate::sort::SortMode;
+use crate::tokenizer::{Normalization, Segmentation, TokenizerConfig, TokenizerKind};
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(name = "noteapp")]
@@ -8,6 +13,81 @@ pub struct Cli {
     #[arg(short, long)]
     pub database: String,
 
+    /// Command used to open `joplin://` note URLs (default: xdg-open)
+    #[arg(long)]
+    pub joplin_opener: Option<String>,
+
+    /// Command used to edit a note exported to a temporary file (default: $VISUAL, $EDITOR, then xdg-open)
+    #[arg(long)]
+    pub editor: Option<String>,
+
+    /// Allow modifying the database, e.g. creating notes. It is only read by default
+    #[arg(long)]
+    pub allow_write: bool,
+
+    /// Start with empty filters and the default layout instead of restoring the last session
+    #[arg(long)]
+    pub fresh: bool,
+
+    /// Start with this query typed into the query box
+    #[arg(long)]
+    pub query: Option<String>,
+
+    /// Start searching note bodies for this text, added to the query as a `b:` term
+    #[arg(long)]
+    pub body_query: Option<String>,
+
+    /// Start with the note with this id selected, if it is listed
+    #[arg(long)]
+    pub select_id: Option<String>,
+
+    /// Print the link to the only matching note without opening the window
+    #[arg(long)]
+    pub select_1: bool,
+
+    /// Exit with status 1 without opening the window when no note matches
+    #[arg(long)]
+    pub exit_0: bool,
+
+    /// Notebook (id or title) new notes are created in (default: that of the last edited note)
+    #[arg(long)]
+    pub notebook: Option<String>,
+
+    /// File picked notes are recorded in for ranking (default: history.tsv in the local data directory)
+    #[arg(long)]
+    pub history_file: Option<std::path::PathBuf>,
+
+    /// File pinned notes are kept in (default: pins.txt in the local data directory)
+    #[arg(long)]
+    pub pins_file: Option<std::path::PathBuf>,
+
+    /// Weight of title matches when ranking search results
+    #[arg(long, default_value_t = FieldWeights::default().title)]
+    pub title_weight: f64,
+
+    /// Weight of body matches when ranking search results
+    #[arg(long, default_value_t = FieldWeights::default().body)]
+    pub body_weight: f64,
+
+    #[command(flatten)]
+    pub tokenizer: TokenizerArgs,
+
+    /// Also list notes in Joplin's trash
+    #[arg(long)]
+    pub include_trashed: bool,
+
+    /// Also list conflict copies of notes
+    #[arg(long)]
+    pub include_conflicts: bool,
+
+    /// Also list notes that are still end-to-end encrypted
+    #[arg(long)]
+    pub include_encrypted: bool,
+
+    /// Directory the picker exports notes to
+    #[arg(long, default_value = "notes-export")]
+    pub export_dir: std::path::PathBuf,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -15,15 +95,134 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Commands {
     /// List all notes
-    List,
+    List {
+        /// Order of the listed notes
+        #[arg(long, value_enum, default_value_t = SortMode::Updated)]
+        sort: SortMode,
+        #[command(flatten)]
+        dates: DateArgs,
+    },
     /// Search notes and Print to stdout
-    /// TODO Implement body or title
     Search {
-        /// Search query
+        /// Search query: words match titles fuzzily, `b:` searches bodies, also
+        /// `nb:`, `tag:`, `is:todo`, `is:done`, `updated:>YYYY-MM-DD`, `-` to exclude
         query: String,
+        /// Order of the matching notes
+        #[arg(long, value_enum, default_value_t = SortMode::Relevance)]
+        sort: SortMode,
+        #[command(flatten)]
+        dates: DateArgs,
     },
     /// Get the Note Content (useful for fzf)
-    Preview {
-        id: String,
+    Preview { id: String },
+    /// List the notes and resources a note links to
+    Links { id: String },
+    /// List the notes linking to a note or resource
+    Backlinks { id: String },
+    /// List open to-dos, soonest due first, with their due dates
+    Todos {
+        /// Include completed to-dos
+        #[arg(long)]
+        all: bool,
+        /// Only to-dos past their due time
+        #[arg(long, conflicts_with = "all")]
+        overdue: bool,
+    },
+    /// Write notes to a directory as Markdown files with YAML front matter
+    Export {
+        /// Directory to write to, created if missing
+        dir: std::path::PathBuf,
+        /// Ids of the notes to export
+        ids: Vec<String>,
+        /// Export the notes matching a search query instead, see `search`
+        #[arg(long, conflicts_with = "ids")]
+        query: Option<String>,
+    },
+    /// Create a note and print a link to it (requires --allow-write)
+    Create {
+        title: String,
+        /// Body of the new note
+        #[arg(long, default_value = "")]
+        body: String,
+    },
+    /// Write a Joplin-shaped database of synthetic notes to --database, for
+    /// tests and benchmarks
+    #[command(hide = true)]
+    GenFixture {
+        /// Number of notes to generate
+        #[arg(long, default_value_t = 1000)]
+        notes: usize,
+        /// Seed of the generator, the same seed gives the same database
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+}
+
+/// Restricts notes to those created or updated within a range of days
+#[derive(Args)]
+pub struct DateArgs {
+    /// Only notes on or after this day (YYYY-MM-DD)
+    #[arg(long)]
+    pub since: Option<NaiveDate>,
+
+    /// Only notes on or before this day (YYYY-MM-DD)
+    #[arg(long)]
+    pub until: Option<NaiveDate>,
+
+    /// Time --since and --until apply to
+    #[arg(long, value_enum, default_value_t = DateField::Updated)]
+    pub date_field: DateField,
+}
+
+impl From<DateArgs> for DateRange {
+    fn from(args: DateArgs) -> Self {
+        DateRange {
+            field: args.date_field,
+            since: args.since,
+            until: args.until,
+        }
+    }
+}
+
+/// How notes and queries are split into terms when ranking
+#[derive(Args)]
+pub struct TokenizerArgs {
+    /// Tokenizer notes are ranked with
+    #[arg(long, value_enum, default_value_t = TokenizerKind::Ngrams)]
+    pub tokenizer: TokenizerKind,
+
+    /// Characters in each n-gram of the ngrams tokenizer
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u8).range(1..=8))]
+    pub ngram_size: u8,
+
+    /// Cut n-grams from each word padded with spaces, rather than across words
+    #[arg(long)]
+    pub pad_words: bool,
+
+    /// Leave out common English words, for the words and stemmed tokenizers
+    #[arg(long)]
+    pub stopwords: bool,
+
+    /// How the ngrams tokenizer splits text
+    #[arg(long, value_enum, default_value_t = Segmentation::Cjk)]
+    pub segmentation: Segmentation,
+
+    /// Tell accented letters apart when ranking, so "cafe" does not find "Café"
+    #[arg(long)]
+    pub keep_diacritics: bool,
+}
+
+impl From<TokenizerArgs> for TokenizerConfig {
+    fn from(args: TokenizerArgs) -> Self {
+        TokenizerConfig {
+            kind: args.tokenizer,
+            ngram_size: args.ngram_size.into(),
+            pad_words: args.pad_words,
+            stopwords: args.stopwords,
+            normalization: Normalization {
+                strip_diacritics: !args.keep_diacritics,
+            },
+            segmentation: args.segmentation,
+        }
     }
 }
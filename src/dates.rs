@@ -0,0 +1,143 @@
+use crate::note::Note;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// A Joplin timestamp, milliseconds since the epoch
+pub fn from_millis(millis: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(millis).unwrap_or_default()
+}
+
+/// A Joplin timestamp that is 0 when unset, e.g. the due time of a to-do
+pub fn optional_from_millis(millis: i64) -> Option<DateTime<Utc>> {
+    (millis != 0).then(|| from_millis(millis))
+}
+
+/// Local midnight at the start of the day
+pub fn day_start(date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map_or_else(|| midnight.and_utc(), |time| time.to_utc())
+}
+
+/// Local midnight at the end of the day
+pub fn day_end(date: NaiveDate) -> DateTime<Utc> {
+    date.succ_opt().map_or(DateTime::<Utc>::MAX_UTC, day_start)
+}
+
+/// Local date and time to the minute, e.g. "2024-03-10 14:05"
+pub fn format_local(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// How long before `now` a time was, e.g. "3 days ago"
+pub fn relative_time(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let seconds = (now - time).num_seconds();
+    let (count, unit) = match seconds {
+        ..60 => return "just now".to_string(),
+        60..3_600 => (seconds / 60, "minute"),
+        3_600..86_400 => (seconds / 3_600, "hour"),
+        86_400..2_592_000 => (seconds / 86_400, "day"),
+        2_592_000..31_536_000 => (seconds / 2_592_000, "month"),
+        _ => (seconds / 31_536_000, "year"),
+    };
+    if count == 1 {
+        format!("1 {unit} ago")
+    } else {
+        format!("{count} {unit}s ago")
+    }
+}
+
+/// Timestamp of a note a [`DateRange`] applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+pub enum DateField {
+    /// When the note was last modified
+    #[default]
+    Updated,
+    /// When the note was created
+    Created,
+}
+
+impl DateField {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DateField::Updated => "Updated",
+            DateField::Created => "Created",
+        }
+    }
+}
+
+/// Days, in local time, a note's created or updated time must fall in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct DateRange {
+    pub field: DateField,
+    /// First day included
+    pub since: Option<NaiveDate>,
+    /// Last day included
+    pub until: Option<NaiveDate>,
+}
+
+impl DateRange {
+    pub fn is_unbounded(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    pub fn contains(&self, note: &Note) -> bool {
+        let time = match self.field {
+            DateField::Updated => note.user_updated_time,
+            DateField::Created => note.user_created_time,
+        };
+        self.since.is_none_or(|since| time >= day_start(since))
+            && self.until.is_none_or(|until| time < day_end(until))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_relative_time() {
+        let now = from_millis(1_700_000_000_000);
+        let ago = |duration: Duration| relative_time(now - duration, now);
+        assert_eq!(ago(Duration::seconds(-5)), "just now");
+        assert_eq!(ago(Duration::seconds(59)), "just now");
+        assert_eq!(ago(Duration::minutes(1)), "1 minute ago");
+        assert_eq!(ago(Duration::hours(5)), "5 hours ago");
+        assert_eq!(ago(Duration::days(3)), "3 days ago");
+        assert_eq!(ago(Duration::days(65)), "2 months ago");
+        assert_eq!(ago(Duration::days(800)), "2 years ago");
+    }
+
+    #[test]
+    fn test_date_range_includes_whole_days() {
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        let mut note = Note {
+            user_created_time: day_start(date("2024-01-01")),
+            user_updated_time: day_end(date("2024-03-10")) - Duration::seconds(1),
+            ..Note::for_test("", "")
+        };
+
+        let range = DateRange {
+            field: DateField::Updated,
+            since: Some(date("2024-03-10")),
+            until: Some(date("2024-03-10")),
+        };
+        assert!(range.contains(&note));
+        note.user_updated_time = day_end(date("2024-03-10"));
+        assert!(!range.contains(&note));
+
+        let created = DateRange {
+            field: DateField::Created,
+            since: None,
+            until: Some(date("2023-12-31")),
+        };
+        assert!(!created.contains(&note));
+        assert!(DateRange::default().contains(&note));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::note::Note;
+use eframe::egui;
+
+pub const EDIT_TITLE_ID: &str = "edit_title_id";
+pub const EDIT_BODY_ID: &str = "edit_body_id";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorAction {
+    Save,
+    /// Save even though the note changed since it was loaded
+    Overwrite,
+    Cancel,
+}
+
+/// In-progress edit of a note's title and body
+pub struct NoteEditor {
+    /// The note as loaded, its `updated_time` is checked again when saving
+    pub original: Note,
+    pub title: String,
+    pub body: String,
+    /// The last save was refused because the note changed elsewhere
+    pub conflict: bool,
+    focus_requested: bool,
+}
+
+impl NoteEditor {
+    pub fn new(note: Note) -> Self {
+        Self {
+            title: note.title.clone(),
+            body: note.body.clone(),
+            original: note,
+            conflict: false,
+            focus_requested: true,
+        }
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.title != self.original.title || self.body != self.original.body
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) -> Option<EditorAction> {
+        let mut action = None;
+        if ctx.input(|i| i.key_pressed(egui::Key::S) && i.modifiers.command) {
+            action = Some(EditorAction::Save);
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("💾 Save").on_hover_text("Ctrl+S").clicked() {
+                action = Some(EditorAction::Save);
+            }
+            if ui.button("Cancel").clicked() {
+                action = Some(EditorAction::Cancel);
+            }
+            if self.is_dirty() {
+                ui.weak("Unsaved changes");
+            }
+        });
+
+        if self.conflict {
+            ui.horizontal(|ui| {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    "This note was changed elsewhere since editing started.",
+                );
+                if ui.button("Overwrite").clicked() {
+                    action = Some(EditorAction::Overwrite);
+                }
+            });
+        }
+
+        ui.add(
+            egui::TextEdit::singleline(&mut self.title)
+                .id(egui::Id::new(EDIT_TITLE_ID))
+                .font(egui::TextStyle::Heading)
+                .desired_width(f32::INFINITY),
+        );
+        ui.separator();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            let body = ui.add(
+                egui::TextEdit::multiline(&mut self.body)
+                    .id(egui::Id::new(EDIT_BODY_ID))
+                    .code_editor()
+                    .desired_width(f32::INFINITY)
+                    .desired_rows(20),
+            );
+            if std::mem::take(&mut self.focus_requested) {
+                body.request_focus();
+            }
+        });
+
+        action
+    }
+}
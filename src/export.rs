@@ -0,0 +1,264 @@
+use crate::folder::Folder;
+use crate::links::{linked_ids, replace_links};
+use crate::note::{open_read_only, table_exists, Note};
+use crate::resource::Resource;
+use chrono::SecondsFormat;
+use rusqlite::{OptionalExtension, Result as SqlResult};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Subdirectory of the export directory resource files are copied to
+const RESOURCES_DIR: &str = "resources";
+
+/// What [`export`] wrote
+#[derive(Debug, Default)]
+pub struct ExportSummary {
+    pub notes: usize,
+    pub resources: usize,
+    /// Ids of linked resources whose files were not found next to the database
+    pub missing_resources: Vec<String>,
+}
+
+/// Write notes to `dir` as Markdown files that make sense without Joplin.
+///
+/// Each note becomes `<title>.md` with its metadata in YAML front matter.
+/// Links between exported notes point to each other's files and linked
+/// resources are copied to `resources/`. Links to notes left out keep their
+/// `:/id` form.
+pub fn export(db_path: &str, notes: &[Note], dir: &Path) -> io::Result<ExportSummary> {
+    let folders = Folder::load_all(db_path).map_err(io::Error::other)?;
+    let tags = tags_by_note(db_path).map_err(io::Error::other)?;
+    let file_names = file_names(notes);
+
+    fs::create_dir_all(dir)?;
+    let mut summary = ExportSummary::default();
+    let mut resources: HashMap<String, Option<String>> = HashMap::new();
+
+    for note in notes {
+        for id in linked_ids(&note.body) {
+            if file_names.contains_key(&id) || resources.contains_key(&id) {
+                continue;
+            }
+            let resource = Resource::get_by_id(db_path, &id)
+                .optional()
+                .map_err(io::Error::other)?;
+            let file_name = match resource {
+                Some(resource) => {
+                    let source = resource.path(db_path);
+                    let file_name = source
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| id.clone());
+                    if source.is_file() {
+                        fs::create_dir_all(dir.join(RESOURCES_DIR))?;
+                        fs::copy(&source, dir.join(RESOURCES_DIR).join(&file_name))?;
+                        summary.resources += 1;
+                    } else {
+                        summary.missing_resources.push(id.clone());
+                    }
+                    Some(file_name)
+                }
+                None => None,
+            };
+            resources.insert(id, file_name);
+        }
+
+        let body = replace_links(&note.body, |id| {
+            if let Some(file_name) = file_names.get(id) {
+                Some(encode_link(file_name))
+            } else {
+                let file_name = resources.get(id)?.as_ref()?;
+                Some(format!("{RESOURCES_DIR}/{}", encode_link(file_name)))
+            }
+        });
+        let no_tags = Vec::new();
+        let front_matter = front_matter(
+            note,
+            &Folder::path(&folders, &note.parent_id),
+            tags.get(&note.id).unwrap_or(&no_tags),
+        );
+        fs::write(
+            dir.join(&file_names[&note.id]),
+            format!("{front_matter}\n{body}"),
+        )?;
+        summary.notes += 1;
+    }
+
+    Ok(summary)
+}
+
+/// File name of each note, from its title and made unique with the id
+fn file_names(notes: &[Note]) -> HashMap<String, String> {
+    let mut names: HashMap<String, String> = HashMap::new();
+    let mut taken: Vec<String> = Vec::new();
+    for note in notes {
+        let stem = safe_file_stem(&note.title);
+        let mut name = format!("{stem}.md");
+        if taken.iter().any(|t| t.eq_ignore_ascii_case(&name)) {
+            name = format!("{stem} {}.md", &note.id[..note.id.len().min(8)]);
+        }
+        taken.push(name.clone());
+        names.insert(note.id.clone(), name);
+    }
+    names
+}
+
+/// A title with the characters file systems reject replaced
+fn safe_file_stem(title: &str) -> String {
+    let stem: String = title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let stem = stem.trim().trim_matches('.');
+    if stem.is_empty() {
+        "Untitled".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+/// Percent-encode a file name for use as a Markdown link target
+fn encode_link(file_name: &str) -> String {
+    let mut encoded = String::with_capacity(file_name.len());
+    for byte in file_name.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+fn front_matter(note: &Note, notebook: &str, tags: &[String]) -> String {
+    let time =
+        |time: chrono::DateTime<chrono::Utc>| time.to_rfc3339_opts(SecondsFormat::Secs, true);
+    let tags: Vec<String> = tags.iter().map(|tag| yaml_string(tag)).collect();
+    format!(
+        "---\nid: {}\ntitle: {}\nnotebook: {}\ntags: [{}]\ncreated: {}\nupdated: {}\n---\n",
+        note.id,
+        yaml_string(&note.title),
+        yaml_string(notebook),
+        tags.join(", "),
+        time(note.user_created_time),
+        time(note.user_updated_time),
+    )
+}
+
+/// Double-quoted YAML scalar
+fn yaml_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Titles of the tags of every tagged note, by note id
+fn tags_by_note(db_path: &str) -> SqlResult<HashMap<String, Vec<String>>> {
+    let conn = open_read_only(db_path)?;
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    if !table_exists(&conn, "tags")? || !table_exists(&conn, "note_tags")? {
+        return Ok(tags);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT note_tags.note_id, tags.title FROM note_tags
+         JOIN tags ON tags.id = note_tags.tag_id
+         ORDER BY tags.title",
+    )?;
+    for row in stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })? {
+        let (note_id, title) = row?;
+        tags.entry(note_id).or_default().push(title);
+    }
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::{new_id, Include};
+    use rusqlite::Connection;
+
+    const A: &str = "0123456789abcdef0123456789abcdef";
+    const B: &str = "fedcba9876543210fedcba9876543210";
+    const R: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const GONE: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+    #[test]
+    fn test_file_names() {
+        assert_eq!(safe_file_stem(" a/b: c? "), "a_b_ c_");
+        assert_eq!(safe_file_stem("..."), "Untitled");
+        assert_eq!(encode_link("Plan (v2).md"), "Plan%20%28v2%29.md");
+        assert_eq!(yaml_string("say \"hi\"\n"), r#""say \"hi\"\n""#);
+    }
+
+    #[test]
+    fn test_export() {
+        let root = std::env::temp_dir().join(format!("chalsedony_export_{}", new_id()));
+        let out = root.join("out");
+        fs::create_dir_all(root.join("resources")).unwrap();
+        fs::write(root.join("resources").join(format!("{R}.png")), b"png").unwrap();
+        let db_path = root.join("database.sqlite");
+        let db_path = db_path.to_str().unwrap();
+        Connection::open(db_path)
+            .unwrap()
+            .execute_batch(&format!(
+                "CREATE TABLE folders (id TEXT, title TEXT, parent_id TEXT);
+                 CREATE TABLE resources (id TEXT, title TEXT, mime TEXT, file_extension TEXT);
+                 CREATE TABLE tags (id TEXT, title TEXT);
+                 CREATE TABLE note_tags (note_id TEXT, tag_id TEXT);
+                 CREATE TABLE notes (id TEXT, parent_id TEXT, title TEXT, body TEXT,
+                                     updated_time INT, user_created_time INT,
+                                     user_updated_time INT, is_todo INT, todo_completed INT,
+                                     todo_due INT, encryption_applied INT);
+                 INSERT INTO folders VALUES ('w', 'Work', ''), ('p', 'Projects', 'w');
+                 INSERT INTO resources VALUES ('{R}', 'diagram', 'image/png', 'png');
+                 INSERT INTO tags VALUES ('t', 'urgent');
+                 INSERT INTO note_tags VALUES ('{A}', 't');
+                 INSERT INTO notes VALUES
+                     ('{A}', 'p', 'Plan: v2', 'See [b](:/{B}) ![d](:/{R}) [gone](:/{GONE})',
+                      0, 0, 0, 0, 0, 0, 0),
+                     ('{B}', 'w', 'Budget', 'Back to [plan](:/{A})', 0, 0, 0, 0, 0, 0, 0);"
+            ))
+            .unwrap();
+
+        let notes = Note::load_all(db_path, Include::default()).unwrap();
+        let summary = export(db_path, &notes, &out).unwrap();
+        assert_eq!((summary.notes, summary.resources), (2, 1));
+        assert!(summary.missing_resources.is_empty());
+
+        let plan = fs::read_to_string(out.join("Plan_ v2.md")).unwrap();
+        assert!(plan.starts_with(&format!("---\nid: {A}\ntitle: \"Plan: v2\"\n")));
+        assert!(plan.contains("notebook: \"Work/Projects\"\ntags: [\"urgent\"]\n"));
+        assert!(plan.contains("created: 1970-01-01T00:00:00Z\n"));
+        assert!(plan.ends_with(&format!(
+            "---\n\nSee [b](Budget.md) ![d](resources/{R}.png) [gone](:/{GONE})"
+        )));
+        let budget = fs::read_to_string(out.join("Budget.md")).unwrap();
+        assert!(budget.ends_with("Back to [plan](Plan_%20v2.md)"));
+        assert_eq!(
+            fs::read(out.join("resources").join(format!("{R}.png"))).unwrap(),
+            b"png"
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,528 @@
+//! Joplin-shaped databases for tests and benchmarks.
+//!
+//! [`Fixture`] builds a database with the tables of a Joplin profile and the
+//! `notes_fts5_porter` full text index, either note by note or as a corpus
+//! of synthetic notes generated from a seed.
+
+use crate::note::{new_id, random_id};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rusqlite::{params, Connection, Result as SqlResult};
+use std::path::{Path, PathBuf};
+
+/// Tables of a Joplin profile database, as far as this program reads them,
+/// and the full text index Chalsedony adds
+const SCHEMA: &str = r#"
+CREATE TABLE folders (
+    id TEXT PRIMARY KEY, title TEXT NOT NULL DEFAULT "",
+    created_time INT NOT NULL, updated_time INT NOT NULL,
+    user_created_time INT NOT NULL DEFAULT 0, user_updated_time INT NOT NULL DEFAULT 0,
+    encryption_cipher_text TEXT NOT NULL DEFAULT "", encryption_applied INT NOT NULL DEFAULT 0,
+    parent_id TEXT NOT NULL DEFAULT "", is_shared INT NOT NULL DEFAULT 0,
+    share_id TEXT NOT NULL DEFAULT "", master_key_id TEXT NOT NULL DEFAULT "",
+    icon TEXT NOT NULL DEFAULT "", user_data TEXT NOT NULL DEFAULT "",
+    deleted_time INT NOT NULL DEFAULT 0
+);
+CREATE TABLE notes (
+    id TEXT PRIMARY KEY, parent_id TEXT NOT NULL DEFAULT "", title TEXT NOT NULL DEFAULT "",
+    body TEXT NOT NULL DEFAULT "", created_time INT NOT NULL, updated_time INT NOT NULL,
+    is_conflict INT NOT NULL DEFAULT 0, latitude NUMERIC NOT NULL DEFAULT 0,
+    longitude NUMERIC NOT NULL DEFAULT 0, altitude NUMERIC NOT NULL DEFAULT 0,
+    author TEXT NOT NULL DEFAULT "", source_url TEXT NOT NULL DEFAULT "",
+    is_todo INT NOT NULL DEFAULT 0, todo_due INT NOT NULL DEFAULT 0,
+    todo_completed INT NOT NULL DEFAULT 0, source TEXT NOT NULL DEFAULT "",
+    source_application TEXT NOT NULL DEFAULT "", application_data TEXT NOT NULL DEFAULT "",
+    `order` NUMERIC NOT NULL DEFAULT 0, user_created_time INT NOT NULL DEFAULT 0,
+    user_updated_time INT NOT NULL DEFAULT 0, encryption_cipher_text TEXT NOT NULL DEFAULT "",
+    encryption_applied INT NOT NULL DEFAULT 0, markup_language INT NOT NULL DEFAULT 1,
+    is_shared INT NOT NULL DEFAULT 0, share_id TEXT NOT NULL DEFAULT "",
+    conflict_original_id TEXT NOT NULL DEFAULT "", master_key_id TEXT NOT NULL DEFAULT "",
+    user_data TEXT NOT NULL DEFAULT "", deleted_time INT NOT NULL DEFAULT 0
+);
+CREATE TABLE tags (
+    id TEXT PRIMARY KEY, title TEXT NOT NULL DEFAULT "",
+    created_time INT NOT NULL, updated_time INT NOT NULL,
+    user_created_time INT NOT NULL DEFAULT 0, user_updated_time INT NOT NULL DEFAULT 0,
+    encryption_cipher_text TEXT NOT NULL DEFAULT "", encryption_applied INT NOT NULL DEFAULT 0,
+    is_shared INT NOT NULL DEFAULT 0, parent_id TEXT NOT NULL DEFAULT ""
+);
+CREATE TABLE note_tags (
+    id TEXT PRIMARY KEY, note_id TEXT NOT NULL, tag_id TEXT NOT NULL,
+    created_time INT NOT NULL, updated_time INT NOT NULL,
+    user_created_time INT NOT NULL DEFAULT 0, user_updated_time INT NOT NULL DEFAULT 0,
+    encryption_cipher_text TEXT NOT NULL DEFAULT "", encryption_applied INT NOT NULL DEFAULT 0,
+    is_shared INT NOT NULL DEFAULT 0
+);
+CREATE TABLE resources (
+    id TEXT PRIMARY KEY, title TEXT NOT NULL DEFAULT "", mime TEXT NOT NULL,
+    filename TEXT NOT NULL DEFAULT "", created_time INT NOT NULL, updated_time INT NOT NULL,
+    user_created_time INT NOT NULL DEFAULT 0, user_updated_time INT NOT NULL DEFAULT 0,
+    file_extension TEXT NOT NULL DEFAULT "", encryption_cipher_text TEXT NOT NULL DEFAULT "",
+    encryption_applied INT NOT NULL DEFAULT 0, encryption_blob_encrypted INT NOT NULL DEFAULT 0,
+    size INT NOT NULL DEFAULT -1, is_shared INT NOT NULL DEFAULT 0,
+    share_id TEXT NOT NULL DEFAULT "", master_key_id TEXT NOT NULL DEFAULT "",
+    user_data TEXT NOT NULL DEFAULT "", blob_updated_time INT NOT NULL DEFAULT 0,
+    ocr_text TEXT NOT NULL DEFAULT "", ocr_details TEXT NOT NULL DEFAULT "",
+    ocr_status INT NOT NULL DEFAULT 0, ocr_error TEXT NOT NULL DEFAULT ""
+);
+CREATE TABLE note_resources (
+    id INTEGER PRIMARY KEY, note_id TEXT NOT NULL, resource_id TEXT NOT NULL,
+    is_associated INT NOT NULL, last_seen_time INT NOT NULL
+);
+CREATE VIRTUAL TABLE notes_fts5_porter USING fts5(
+    id, title, body, content='notes', content_rowid='rowid', tokenize = 'porter ascii'
+);
+CREATE TRIGGER notes_ai AFTER INSERT ON notes BEGIN
+    INSERT INTO notes_fts5_porter(rowid, title, body) VALUES (new.rowid, new.title, new.body);
+END;
+CREATE TRIGGER notes_ad AFTER DELETE ON notes BEGIN
+    INSERT INTO notes_fts5_porter(notes_fts5_porter, rowid, title, body)
+    VALUES ('delete', old.rowid, old.title, old.body);
+END;
+CREATE TRIGGER notes_au AFTER UPDATE ON notes BEGIN
+    INSERT INTO notes_fts5_porter(notes_fts5_porter, rowid, title, body)
+    VALUES ('delete', old.rowid, old.title, old.body);
+    INSERT INTO notes_fts5_porter(rowid, title, body) VALUES (new.rowid, new.title, new.body);
+END;
+"#;
+
+/// Words synthetic notes are written with, roughly most common first
+const WORDS: &[&str] = &[
+    "the",
+    "and",
+    "notes",
+    "project",
+    "meeting",
+    "plan",
+    "review",
+    "idea",
+    "list",
+    "draft",
+    "budget",
+    "report",
+    "week",
+    "team",
+    "data",
+    "design",
+    "research",
+    "paper",
+    "code",
+    "rust",
+    "python",
+    "database",
+    "query",
+    "search",
+    "index",
+    "ranking",
+    "garden",
+    "recipe",
+    "travel",
+    "book",
+    "reading",
+    "summary",
+    "question",
+    "answer",
+    "deadline",
+    "client",
+    "invoice",
+    "schedule",
+    "holiday",
+    "family",
+    "health",
+    "exercise",
+    "running",
+    "coffee",
+    "kitchen",
+    "shopping",
+    "groceries",
+    "library",
+    "server",
+    "deploy",
+    "release",
+    "bug",
+    "feature",
+    "refactor",
+    "test",
+    "benchmark",
+    "latency",
+    "memory",
+    "cache",
+    "network",
+    "linux",
+    "keyboard",
+    "editor",
+    "terminal",
+    "shell",
+    "script",
+    "config",
+    "backup",
+    "archive",
+    "photo",
+    "music",
+    "guitar",
+    "piano",
+    "lecture",
+    "course",
+    "exam",
+    "thesis",
+    "chapter",
+    "statistics",
+    "regression",
+    "matrix",
+    "vector",
+    "gradient",
+    "model",
+    "training",
+    "experiment",
+    "results",
+    "figure",
+    "table",
+    "appendix",
+    "reference",
+    "citation",
+    "conference",
+    "workshop",
+    "journal",
+    "submission",
+    "feedback",
+    "revision",
+    "approval",
+    "contract",
+    "proposal",
+    "quarterly",
+    "annual",
+    "strategy",
+    "roadmap",
+    "milestone",
+    "retrospective",
+    "onboarding",
+    "hiring",
+    "interview",
+    "salary",
+    "tax",
+    "insurance",
+    "mortgage",
+    "renovation",
+    "plumbing",
+    "electrician",
+    "tomatoes",
+    "basil",
+    "compost",
+    "watering",
+    "seedlings",
+    "harvest",
+    "bicycle",
+    "mountain",
+    "hiking",
+    "camping",
+    "passport",
+    "flight",
+    "hotel",
+    "itinerary",
+    "museum",
+    "birthday",
+    "anniversary",
+    "gift",
+    "wedding",
+    "dentist",
+    "pharmacy",
+    "vaccination",
+    "sourdough",
+    "fermentation",
+    "espresso",
+    "chalsedony",
+    "joplin",
+    "markdown",
+    "trigram",
+    "tokenizer",
+    "levenshtein",
+];
+
+/// Tags synthetic notes are given
+const TAGS: &[&str] = &[
+    "work",
+    "personal",
+    "urgent",
+    "someday",
+    "reference",
+    "reading",
+    "research",
+    "home",
+    "finance",
+    "health",
+    "travel",
+    "ideas",
+];
+
+/// A Joplin-shaped database, in a temporary file removed when dropped or in
+/// a file of its own.
+///
+/// Every item added is a minute newer than the one before, so the order
+/// they were added in is the order of their times. Ids come from a seeded
+/// generator, so the same calls give the same database.
+pub struct Fixture {
+    path: PathBuf,
+    conn: Connection,
+    time: i64,
+    rng: StdRng,
+    temporary: bool,
+}
+
+impl Fixture {
+    /// An empty database in a temporary file
+    pub fn temporary() -> SqlResult<Self> {
+        let path = std::env::temp_dir().join(format!("chalsedony_fixture_{}.sqlite", new_id()));
+        let mut fixture = Self::create(&path, 0)?;
+        fixture.temporary = true;
+        Ok(fixture)
+    }
+
+    /// An empty database at `path`, which is kept
+    pub fn create(path: &Path, seed: u64) -> SqlResult<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            conn,
+            time: 1_700_000_000_000,
+            rng: StdRng::seed_from_u64(seed),
+            temporary: false,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        self.path.to_str().expect("fixture path is not UTF-8")
+    }
+
+    fn tick(&mut self) -> i64 {
+        self.time += 60_000;
+        self.time
+    }
+
+    /// Add a notebook, returning its id
+    pub fn folder(&mut self, title: &str, parent_id: &str) -> SqlResult<String> {
+        let id = random_id(&mut self.rng);
+        let time = self.tick();
+        self.conn
+            .prepare_cached(
+                "INSERT INTO folders (id, title, parent_id, created_time, updated_time,
+                                      user_created_time, user_updated_time)
+                 VALUES (?1, ?2, ?3, ?4, ?4, ?4, ?4)",
+            )?
+            .execute(params![id, title, parent_id, time])?;
+        Ok(id)
+    }
+
+    /// Add a note, returning its id
+    pub fn note(&mut self, title: &str, body: &str, parent_id: &str) -> SqlResult<String> {
+        let id = random_id(&mut self.rng);
+        let time = self.tick();
+        self.conn
+            .prepare_cached(
+                "INSERT INTO notes (id, parent_id, title, body, created_time, updated_time,
+                                    user_created_time, user_updated_time, \"order\")
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?5, ?5, ?5, ?5)",
+            )?
+            .execute(params![id, parent_id, title, body, time])?;
+        Ok(id)
+    }
+
+    /// Add a tag, returning its id
+    pub fn tag(&mut self, title: &str) -> SqlResult<String> {
+        let id = random_id(&mut self.rng);
+        let time = self.tick();
+        self.conn
+            .prepare_cached(
+                "INSERT INTO tags (id, title, created_time, updated_time,
+                                   user_created_time, user_updated_time)
+                 VALUES (?1, ?2, ?3, ?3, ?3, ?3)",
+            )?
+            .execute(params![id, title, time])?;
+        Ok(id)
+    }
+
+    pub fn tag_note(&mut self, note_id: &str, tag_id: &str) -> SqlResult<()> {
+        let id = random_id(&mut self.rng);
+        let time = self.tick();
+        self.conn
+            .prepare_cached(
+                "INSERT INTO note_tags (id, note_id, tag_id, created_time, updated_time,
+                                        user_created_time, user_updated_time)
+                 VALUES (?1, ?2, ?3, ?4, ?4, ?4, ?4)",
+            )?
+            .execute(params![id, note_id, tag_id, time])?;
+        Ok(())
+    }
+
+    /// Add a resource, returning its id. Only the row is written, not the file.
+    pub fn resource(&mut self, title: &str, mime: &str, extension: &str) -> SqlResult<String> {
+        let id = random_id(&mut self.rng);
+        let time = self.tick();
+        self.conn
+            .prepare_cached(
+                "INSERT INTO resources (id, title, mime, filename, file_extension,
+                                        created_time, updated_time,
+                                        user_created_time, user_updated_time)
+                 VALUES (?1, ?2, ?3, ?2, ?4, ?5, ?5, ?5, ?5)",
+            )?
+            .execute(params![id, title, mime, extension, time])?;
+        Ok(id)
+    }
+
+    /// Run SQL against the database, e.g. to turn a note into a to-do
+    pub fn execute(&self, sql: &str) -> SqlResult<()> {
+        self.conn.execute_batch(sql)
+    }
+
+    /// Add `count` synthetic notes with notebooks, tags, resources and to-dos.
+    ///
+    /// Words are drawn so that some are much more common than others, as in
+    /// real notes, which gives rankings something to separate. Notes link to
+    /// earlier notes and some embed a resource.
+    pub fn generate(&mut self, count: usize) -> SqlResult<()> {
+        self.conn.execute_batch("BEGIN")?;
+        let result = self.populate(count);
+        self.conn
+            .execute_batch(if result.is_ok() { "COMMIT" } else { "ROLLBACK" })?;
+        result
+    }
+
+    fn populate(&mut self, count: usize) -> SqlResult<()> {
+        let mut folders: Vec<String> = Vec::new();
+        for _ in 0..count / 100 + 1 {
+            let title = self.sentence(1, 2);
+            let parent = match folders.choose(&mut self.rng) {
+                Some(parent) if self.rng.gen_bool(0.3) => parent.clone(),
+                _ => String::new(),
+            };
+            folders.push(self.folder(&title, &parent)?);
+        }
+        let tags = TAGS
+            .iter()
+            .map(|title| self.tag(title))
+            .collect::<SqlResult<Vec<_>>>()?;
+        let mut resources = Vec::new();
+        for _ in 0..count / 20 + 1 {
+            let title = format!("{}.png", self.word());
+            resources.push(self.resource(&title, "image/png", "png")?);
+        }
+
+        let mut notes: Vec<(String, String)> = Vec::with_capacity(count);
+        for _ in 0..count {
+            let title = self.sentence(2, 6);
+            let mut body = String::new();
+            for _ in 0..self.rng.gen_range(1..=5) {
+                if self.rng.gen_bool(0.3) {
+                    body.push_str(&format!("## {}\n\n", self.sentence(1, 4)));
+                }
+                body.push_str(&self.sentence(10, 80));
+                body.push_str(".\n\n");
+            }
+            if let Some((id, title)) = notes.choose(&mut self.rng) {
+                if self.rng.gen_bool(0.2) {
+                    body.push_str(&format!("See [{title}](:/{id}).\n"));
+                }
+            }
+            if self.rng.gen_bool(0.05) {
+                let resource = resources
+                    .choose(&mut self.rng)
+                    .expect("resources are added");
+                body.push_str(&format!("![diagram](:/{resource})\n"));
+            }
+
+            let folder = folders
+                .choose(&mut self.rng)
+                .expect("folders are added")
+                .clone();
+            let id = self.note(&title, &body, &folder)?;
+            let tag_count = self.rng.gen_range(0..=3);
+            let note_tags: Vec<String> = tags
+                .choose_multiple(&mut self.rng, tag_count)
+                .cloned()
+                .collect();
+            for tag in &note_tags {
+                self.tag_note(&id, tag)?;
+            }
+            if self.rng.gen_bool(0.1) {
+                let due = self.time + self.rng.gen_range(-30..=30) * 86_400_000;
+                let completed = if self.rng.gen_bool(0.5) { self.time } else { 0 };
+                self.conn
+                    .prepare_cached(
+                        "UPDATE notes SET is_todo = 1, todo_due = ?1, todo_completed = ?2
+                         WHERE id = ?3",
+                    )?
+                    .execute(params![due, completed, id])?;
+            }
+            notes.push((id, title));
+        }
+        Ok(())
+    }
+
+    /// A word, with common words much likelier than rare ones
+    fn word(&mut self) -> &'static str {
+        let x: f64 = self.rng.gen();
+        WORDS[((x * x * x) * WORDS.len() as f64) as usize]
+    }
+
+    /// Between `min` and `max` words, the first capitalised
+    fn sentence(&mut self, min: usize, max: usize) -> String {
+        let words: Vec<&str> = (0..self.rng.gen_range(min..=max))
+            .map(|_| self.word())
+            .collect();
+        let sentence = words.join(" ");
+        let mut chars = sentence.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => sentence,
+        }
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::{Include, Note};
+    use crate::query::Query;
+
+    fn corpus() -> Vec<Note> {
+        let mut fixture = Fixture::temporary().unwrap();
+        fixture.generate(200).unwrap();
+        Note::load_all(fixture.path(), Include::default()).unwrap()
+    }
+
+    #[test]
+    fn test_generate_is_deterministic() {
+        let notes = corpus();
+        assert_eq!(notes.len(), 200);
+        let again = corpus();
+        let ids = |notes: &[Note]| notes.iter().map(|n| n.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&notes), ids(&again));
+        assert!(notes.iter().any(|n| n.is_todo));
+    }
+
+    #[test]
+    fn test_generated_notes_are_searchable() {
+        let mut fixture = Fixture::temporary().unwrap();
+        fixture.generate(200).unwrap();
+        let note = &Note::load_all(fixture.path(), Include::default()).unwrap()[0];
+        let word = note.body.split_whitespace().nth(2).unwrap();
+        let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+
+        let find = |query: &str| {
+            let query = Query::parse(query).unwrap();
+            Note::find(fixture.path(), &query, Include::default()).unwrap()
+        };
+        assert!(find(&format!("b:{word}")).iter().any(|n| n.id == note.id));
+        let tagged = find("tag:work").len();
+        assert!(tagged > 0 && tagged < 200);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::note::open_read_only;
+use rusqlite::{OptionalExtension, Result as SqlResult};
+
+/// A Joplin notebook, stored in the `folders` table
+#[derive(Debug, Clone)]
+pub struct Folder {
+    pub id: String,
+    pub title: String,
+    /// Id of the notebook this one is nested in, empty at the top level
+    pub parent_id: String,
+}
+
+impl Folder {
+    pub fn load_all(db_path: &str) -> SqlResult<Vec<Folder>> {
+        let conn = open_read_only(db_path)?;
+
+        let mut stmt = conn.prepare("SELECT id, title, parent_id FROM folders ORDER BY title")?;
+        let folder_iter = stmt.query_map([], Self::from_row)?;
+
+        let mut folders = Vec::new();
+        for folder in folder_iter {
+            folders.push(folder?);
+        }
+
+        Ok(folders)
+    }
+
+    /// Find a notebook by id, falling back to a case-insensitive title match
+    pub fn find(db_path: &str, id_or_title: &str) -> SqlResult<Option<Folder>> {
+        let folders = Self::load_all(db_path)?;
+        let found = folders
+            .iter()
+            .find(|f| f.id == id_or_title)
+            .or_else(|| {
+                folders
+                    .iter()
+                    .find(|f| f.title.to_lowercase() == id_or_title.to_lowercase())
+            })
+            .cloned();
+        Ok(found)
+    }
+
+    /// The notebook named on the command line, or the default one
+    pub fn resolve(db_path: &str, id_or_title: Option<&str>) -> SqlResult<Option<Folder>> {
+        match id_or_title {
+            Some(id_or_title) => Self::find(db_path, id_or_title),
+            None => Self::default_for_new_notes(db_path),
+        }
+    }
+
+    /// Notebook new notes go into when none is given.
+    ///
+    /// This is the notebook of the most recently edited note, which is usually
+    /// the one being worked in, or the first notebook if there are no notes.
+    pub fn default_for_new_notes(db_path: &str) -> SqlResult<Option<Folder>> {
+        let conn = open_read_only(db_path)?;
+
+        conn.query_row(
+            "SELECT id, title, parent_id FROM folders
+             ORDER BY (SELECT max(user_updated_time) FROM notes WHERE parent_id = folders.id) DESC,
+                      title
+             LIMIT 1",
+            [],
+            Self::from_row,
+        )
+        .optional()
+    }
+
+    fn from_row(row: &rusqlite::Row) -> SqlResult<Folder> {
+        Ok(Folder {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            parent_id: row.get(2)?,
+        })
+    }
+
+    /// Titles of the notebook with the given id and those it is nested in,
+    /// outermost first and joined by `/`, e.g. `Work/Projects`
+    pub fn path(folders: &[Folder], id: &str) -> String {
+        let mut titles = Vec::new();
+        let mut current = id;
+        // Bounded, in case a corrupt database has a cycle of parents
+        while let Some(folder) = folders.iter().find(|f| f.id == current) {
+            if titles.len() > folders.len() {
+                break;
+            }
+            titles.push(folder.title.as_str());
+            current = &folder.parent_id;
+        }
+        titles.reverse();
+        titles.join("/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(id: &str, title: &str, parent_id: &str) -> Folder {
+        Folder {
+            id: id.to_string(),
+            title: title.to_string(),
+            parent_id: parent_id.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_path() {
+        let folders = vec![
+            folder("w", "Work", ""),
+            folder("p", "Projects", "w"),
+            folder("x", "Loop", "y"),
+            folder("y", "Back", "x"),
+        ];
+        assert_eq!(Folder::path(&folders, "p"), "Work/Projects");
+        assert_eq!(Folder::path(&folders, "w"), "Work");
+        assert_eq!(Folder::path(&folders, "missing"), "");
+        assert!(Folder::path(&folders, "x").ends_with("Back/Loop"));
+    }
+}
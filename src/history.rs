@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Days for the weight of a pick to halve in the frecency score
+const HALF_LIFE_DAYS: f64 = 14.0;
+
+/// Notes picked in the past, kept outside Joplin's database.
+///
+/// Stored as one `<note id>\t<unix seconds>` line per pick, appended to as
+/// notes are picked, so the file can be inspected or trimmed by hand.
+#[derive(Debug, Default)]
+pub struct History {
+    path: Option<PathBuf>,
+    picks: Vec<(String, i64)>,
+}
+
+impl History {
+    /// `history.tsv` in the platform's local data directory
+    pub fn default_path() -> Option<PathBuf> {
+        Some(
+            dirs::data_local_dir()?
+                .join("chalsedony_selector")
+                .join("history.tsv"),
+        )
+    }
+
+    /// Read the history file, a missing file is an empty history
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let picks = contents
+            .lines()
+            .filter_map(|line| {
+                let (id, time) = line.split_once('\t')?;
+                Some((id.to_string(), time.trim().parse().ok()?))
+            })
+            .collect();
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            picks,
+        })
+    }
+
+    /// Remember that a note was picked at `time` (unix seconds)
+    pub fn record(&mut self, note_id: &str, time: i64) -> io::Result<()> {
+        self.picks.push((note_id.to_string(), time));
+
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{note_id}\t{time}")
+    }
+
+    /// Frecency of every picked note at `now` (unix seconds).
+    ///
+    /// Each pick counts 1 when fresh and half as much every [`HALF_LIFE_DAYS`],
+    /// so notes picked often and recently score highest.
+    pub fn frecency(&self, now: i64) -> HashMap<String, f64> {
+        let mut scores: HashMap<String, f64> = HashMap::new();
+        for (id, time) in &self.picks {
+            let age_days = (now - time).max(0) as f64 / 86_400.0;
+            *scores.entry(id.clone()).or_default() += 0.5_f64.powf(age_days / HALF_LIFE_DAYS);
+        }
+        scores
+    }
+
+    /// Ids of the last `limit` distinct notes picked, most recent first
+    pub fn recent(&self, limit: usize) -> Vec<String> {
+        let mut recent: Vec<String> = Vec::new();
+        for (id, _) in self.picks.iter().rev() {
+            if recent.len() == limit {
+                break;
+            }
+            if !recent.contains(id) {
+                recent.push(id.clone());
+            }
+        }
+        recent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 86_400;
+
+    #[test]
+    fn test_frecency_prefers_recent_and_frequent() {
+        let mut history = History::default();
+        let now = 100 * DAY;
+        history.record("old", now - 60 * DAY).unwrap();
+        history.record("recent", now - DAY).unwrap();
+        history.record("frequent", now - 10 * DAY).unwrap();
+        history.record("frequent", now - 10 * DAY).unwrap();
+
+        let scores = history.frecency(now);
+        assert!(scores["recent"] > scores["old"]);
+        assert!(scores["frequent"] > scores["recent"]);
+        assert!((scores["recent"] - 0.5_f64.powf(1.0 / HALF_LIFE_DAYS)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_recent_is_distinct_and_newest_first() {
+        let mut history = History::default();
+        for (id, time) in [("a", 1), ("b", 2), ("a", 3), ("c", 4), ("d", 5)] {
+            history.record(id, time).unwrap();
+        }
+        assert_eq!(history.recent(3), vec!["d", "c", "a"]);
+        assert_eq!(history.recent(10), vec!["d", "c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_round_trip_through_file() {
+        let path = std::env::temp_dir()
+            .join(format!("chalsedony_history_{}", crate::note::new_id()))
+            .join("history.tsv");
+        let mut history = History::load(&path).unwrap();
+        assert!(history.frecency(0).is_empty());
+
+        history.record("a", 10).unwrap();
+        history.record("b", 20).unwrap();
+        let reloaded = History::load(&path).unwrap();
+        assert_eq!(
+            reloaded.picks,
+            vec![("a".to_string(), 10), ("b".to_string(), 20)]
+        );
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,23 @@
+//! Search and link Joplin notes, shared by the picker binary and the benchmarks.
+
+pub mod bm25;
+pub mod cli;
+pub mod dates;
+pub mod editor;
+pub mod export;
+pub mod fixture;
+pub mod folder;
+pub mod history;
+pub mod links;
+pub mod list;
+pub mod markdown;
+pub mod mode;
+pub mod note;
+pub mod open;
+pub mod pins;
+pub mod query;
+pub mod resource;
+pub mod sort;
+pub mod tokenizer;
+pub mod typo;
+pub mod ui;
@@ -0,0 +1,100 @@
+/// Length of a Joplin item id, a UUID without dashes
+pub const ID_LEN: usize = 32;
+
+/// Ids referenced by `:/id` links in a note body, in order of first appearance.
+///
+/// Joplin ids are 32 lowercase hex characters, anything else after `:/` is ignored.
+pub fn linked_ids(body: &str) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::new();
+    for (start, _) in body.match_indices(":/") {
+        if let Some(id) = id_at(&body[start + 2..]) {
+            if !ids.iter().any(|seen| seen == id) {
+                ids.push(id.to_string());
+            }
+        }
+    }
+    ids
+}
+
+/// Replace each `:/id` link target with what `replace` returns for the id,
+/// leaving those it returns `None` for as they are
+pub fn replace_links(body: &str, mut replace: impl FnMut(&str) -> Option<String>) -> String {
+    let mut replaced = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(start) = rest.find(":/") {
+        let target = id_at(&rest[start + 2..]).and_then(&mut replace);
+        match target {
+            Some(target) => {
+                replaced.push_str(&rest[..start]);
+                replaced.push_str(&target);
+                rest = &rest[start + 2 + ID_LEN..];
+            }
+            None => {
+                replaced.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+            }
+        }
+    }
+    replaced.push_str(rest);
+    replaced
+}
+
+/// Whether `text` is a whole Joplin id
+pub fn is_id(text: &str) -> bool {
+    text.len() == ID_LEN && id_at(text).is_some()
+}
+
+/// The id at the start of the text following `:/`, if it is one
+fn id_at(candidate: &str) -> Option<&str> {
+    let is_id = candidate.len() >= ID_LEN
+        && candidate.as_bytes()[..ID_LEN]
+            .iter()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(b))
+        && !candidate.as_bytes()[ID_LEN..]
+            .first()
+            .is_some_and(|b| b.is_ascii_alphanumeric());
+    is_id.then(|| &candidate[..ID_LEN])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: &str = "0123456789abcdef0123456789abcdef";
+    const B: &str = "fedcba9876543210fedcba9876543210";
+
+    #[test]
+    fn test_markdown_links() {
+        let body = format!("See [a](:/{A}) and ![b](:/{B}).");
+        assert_eq!(linked_ids(&body), vec![A, B]);
+    }
+
+    #[test]
+    fn test_duplicates_keep_first_order() {
+        let body = format!("[x](:/{B}) [y](:/{A}) [z](:/{B})");
+        assert_eq!(linked_ids(&body), vec![B, A]);
+    }
+
+    #[test]
+    fn test_heading_anchor_and_html() {
+        let body = format!("[x](:/{A}#intro) <img src=\":/{B}\"/>");
+        assert_eq!(linked_ids(&body), vec![A, B]);
+    }
+
+    #[test]
+    fn test_rejects_non_ids() {
+        let upper = A.to_uppercase();
+        let body = format!("https://example.com :/short :/{upper} :/{A}0");
+        assert!(linked_ids(&body).is_empty());
+    }
+
+    #[test]
+    fn test_replace_links() {
+        let body = format!("[a](:/{A}#intro) [b](:/{B}) :/short [a again](:/{A})");
+        let replaced = replace_links(&body, |id| (id == A).then(|| "a.md".to_string()));
+        assert_eq!(
+            replaced,
+            format!("[a](a.md#intro) [b](:/{B}) :/short [a again](a.md)")
+        );
+    }
+}
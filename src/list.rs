@@ -1,7 +1,12 @@
+use crate::dates::{format_local, relative_time};
+use crate::editor::{EditorAction, NoteEditor};
+use crate::markdown::MarkdownViewer;
+use crate::mode::Mode;
 use crate::note::Note;
 use eframe::egui;
-use egui_demo_lib::easy_mark::easy_mark;
-use unindent::unindent;
+
+/// Notes of the pinned and recent section that can be picked with a number key
+pub const QUICK_KEYS: usize = 9;
 
 #[derive(Debug, Clone, Copy)]
 pub enum Direction {
@@ -9,20 +14,44 @@ pub enum Direction {
     Down,
 }
 
-pub trait UIMarkdown {
-    fn markdown(&mut self, markdown: &str);
-}
-
-impl UIMarkdown for egui::Ui {
-    fn markdown(&mut self, markdown: &str) {
-        easy_mark(self, &unindent(markdown));
-    }
+/// What happened in [`SelectableList::show`] that the caller has to act on
+#[derive(Debug, Default)]
+pub struct ListResponse {
+    /// Id from a `:/id` link clicked in the preview
+    pub clicked_link: Option<String>,
+    /// The edit button of the preview was clicked
+    pub edit_requested: bool,
+    /// The to-do checkbox of the preview was clicked
+    pub toggle_completed: bool,
+    pub editor_action: Option<EditorAction>,
 }
 
 pub struct SelectableList {
     pub items: Vec<Note>,
     pub selected_item: Option<usize>,
     pub show_preview_under: bool,
+    /// Number of rows visible in the list, used for half page scrolling
+    page_size: usize,
+    /// First `g` of a `gg` has been pressed
+    pending_g: bool,
+    /// Selection was moved from the keyboard and should be scrolled into view
+    scroll_to_selected: bool,
+    /// Note reached by following links in the preview, shown instead of the selection
+    navigated: Option<Note>,
+    /// Notes visited before `navigated`, most recent last
+    back_stack: Vec<Note>,
+    /// Notes left by going back, most recent last
+    forward_stack: Vec<Note>,
+    /// Selection the link history belongs to, history is dropped when it changes
+    navigation_origin: Option<usize>,
+    /// Notes linking to the previewed note, keyed by the previewed note's id
+    backlinks: Option<(String, Vec<Note>)>,
+    /// Shown in place of the preview while a note is being edited
+    pub editor: Option<NoteEditor>,
+    /// Pinned notes, shown above the list with the recently picked ones
+    pub pinned: Vec<Note>,
+    /// Recently picked notes that aren't pinned, most recent first
+    pub recent: Vec<Note>,
 }
 
 impl SelectableList {
@@ -31,7 +60,106 @@ impl SelectableList {
             items,
             selected_item: None,
             show_preview_under: false,
+            page_size: 10,
+            pending_g: false,
+            scroll_to_selected: false,
+            navigated: None,
+            back_stack: Vec::new(),
+            forward_stack: Vec::new(),
+            navigation_origin: None,
+            backlinks: None,
+            editor: None,
+            pinned: Vec::new(),
+            recent: Vec::new(),
+        }
+    }
+
+    /// The `n`th note of the pinned and recent section, counting from 0
+    pub fn quick_note(&self, n: usize) -> Option<&Note> {
+        self.pinned.iter().chain(&self.recent).nth(n)
+    }
+
+    pub fn is_editing(&self) -> bool {
+        self.editor.is_some()
+    }
+
+    /// Close the editor and show the saved version of the note everywhere it appears
+    pub fn finish_editing(&mut self, saved: Note) {
+        self.editor = None;
+        self.replace_note(saved);
+    }
+
+    /// Show the updated version of a note wherever it is listed or previewed
+    pub fn replace_note(&mut self, updated: Note) {
+        for note in self
+            .items
+            .iter_mut()
+            .chain(self.navigated.iter_mut())
+            .filter(|note| note.id == updated.id)
+        {
+            *note = updated.clone();
+        }
+    }
+
+    /// The note selected in the list, which links are made to
+    pub fn selected(&self) -> Option<&Note> {
+        self.selected_item.and_then(|i| self.items.get(i))
+    }
+
+    /// The note shown in the preview pane
+    pub fn previewed(&self) -> Option<&Note> {
+        self.navigated.as_ref().or_else(|| self.selected())
+    }
+
+    /// Show `note` in the preview, remembering the current note for going back
+    pub fn navigate_to(&mut self, note: Note) {
+        if let Some(current) = self.previewed().cloned() {
+            self.back_stack.push(current);
         }
+        self.forward_stack.clear();
+        self.navigation_origin = self.selected_item;
+        self.navigated = Some(note);
+    }
+
+    pub fn navigate_back(&mut self) {
+        if let Some(note) = self.back_stack.pop() {
+            if let Some(current) = self.navigated.take() {
+                self.forward_stack.push(current);
+            }
+            // Back at the selected note once the stack is exhausted
+            if self.back_stack.is_empty() {
+                return;
+            }
+            self.navigated = Some(note);
+        }
+    }
+
+    pub fn navigate_forward(&mut self) {
+        if let Some(note) = self.forward_stack.pop() {
+            if let Some(current) = self.previewed().cloned() {
+                self.back_stack.push(current);
+            }
+            self.navigated = Some(note);
+        }
+    }
+
+    fn clear_navigation(&mut self) {
+        self.navigated = None;
+        self.back_stack.clear();
+        self.forward_stack.clear();
+    }
+
+    /// Id of the previewed note if its backlinks still need to be loaded
+    pub fn backlinks_pending(&self) -> Option<String> {
+        let id = &self.previewed()?.id;
+        match &self.backlinks {
+            Some((loaded, _)) if loaded == id => None,
+            _ => Some(id.clone()),
+        }
+    }
+
+    pub fn set_backlinks(&mut self, note_id: String, notes: Vec<Note>) {
+        self.backlinks = Some((note_id, notes));
     }
 
     pub fn move_selection(&mut self, direction: Direction) {
@@ -47,35 +175,151 @@ impl SelectableList {
             }
             _ => {}
         }
+        self.scroll_to_selected = true;
+    }
+
+    /// Move the selection by `count` rows, stopping at either end of the list
+    pub fn move_selection_by(&mut self, direction: Direction, count: usize) {
+        for _ in 0..count {
+            self.move_selection(direction);
+        }
+    }
+
+    /// Select the note with the given id if it is listed
+    pub fn select_id(&mut self, id: &str) {
+        if let Some(i) = self.items.iter().position(|note| note.id == id) {
+            self.selected_item = Some(i);
+            self.scroll_to_selected = true;
+        }
+    }
+
+    pub fn select_first(&mut self) {
+        if !self.items.is_empty() {
+            self.selected_item = Some(0);
+            self.scroll_to_selected = true;
+        }
+    }
+
+    pub fn select_last(&mut self) {
+        if !self.items.is_empty() {
+            self.selected_item = Some(self.items.len() - 1);
+            self.scroll_to_selected = true;
+        }
+    }
+
+    /// Handle list navigation keys.
+    ///
+    /// Up and down arrows work in every mode, the vim bindings (`j`, `k`, `gg`,
+    /// `G`, `Ctrl+D`, `Ctrl+U`) and `Alt+←`/`Alt+→` only in normal mode, so they
+    /// can be typed into filters.
+    fn handle_keys(&mut self, ctx: &egui::Context, mode: Mode) {
+        if ctx.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
+            self.move_selection(Direction::Down);
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::ArrowUp)) {
+            self.move_selection(Direction::Up);
+        }
+
+        if mode != Mode::Normal {
+            self.pending_g = false;
+            return;
+        }
+
+        // Alt+arrows move by word in the query box, so only outside of it
+        if ctx.input(|i| i.key_pressed(egui::Key::ArrowLeft) && i.modifiers.alt) {
+            self.navigate_back();
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::ArrowRight) && i.modifiers.alt) {
+            self.navigate_forward();
+        }
+
+        let half_page = (self.page_size / 2).max(1);
+        ctx.input(|i| {
+            let key_pressed = i
+                .events
+                .iter()
+                .any(|e| matches!(e, egui::Event::Key { pressed: true, .. }));
+            if !key_pressed {
+                return;
+            }
+
+            // Any key other than a second `g` cancels a pending `g`
+            let pending_g = std::mem::take(&mut self.pending_g);
+            if i.key_pressed(egui::Key::D) && i.modifiers.command_only() {
+                self.move_selection_by(Direction::Down, half_page);
+            } else if i.key_pressed(egui::Key::U) && i.modifiers.command_only() {
+                self.move_selection_by(Direction::Up, half_page);
+            } else if i.key_pressed(egui::Key::G) && i.modifiers.shift_only() {
+                self.select_last();
+            } else if i.key_pressed(egui::Key::G) && i.modifiers.is_none() {
+                if pending_g {
+                    self.select_first();
+                } else {
+                    self.pending_g = true;
+                }
+            } else if i.key_pressed(egui::Key::J) && i.modifiers.is_none() {
+                self.move_selection(Direction::Down);
+            } else if i.key_pressed(egui::Key::K) && i.modifiers.is_none() {
+                self.move_selection(Direction::Up);
+            }
+        });
     }
 
     pub fn copy_selected_to_clipboard(&self, ctx: &egui::Context) {
-        if let Some(selected) = self.selected_item {
-            let note = &self.items[selected];
+        if let Some(note) = self.selected() {
             let text = format!("# {}\n\n{}", note.title, note.body);
             ctx.output_mut(|o| o.copied_text = text);
         }
     }
 
-    pub fn print_selected(&self) {
-        if let Some(selected) = self.selected_item {
-            let note = &self.items[selected];
-            println!("[{}](:/{})", note.title, note.id);
+    /// The pinned and recent section, returns the note clicked
+    fn show_quick_notes(&self, ui: &mut egui::Ui) -> Option<Note> {
+        if self.pinned.is_empty() && self.recent.is_empty() {
+            return None;
         }
+        let previewed = self.previewed().map(|note| note.id.as_str());
+        let mut clicked = None;
+        egui::CollapsingHeader::new("Pinned and recent")
+            .default_open(true)
+            .show(ui, |ui| {
+                let pinned = self.pinned.iter().map(|note| (note, "📌 "));
+                let recent = self.recent.iter().map(|note| (note, "🕑 "));
+                for (i, (note, icon)) in pinned.chain(recent).enumerate() {
+                    let number = if i < QUICK_KEYS {
+                        format!("{} ", i + 1)
+                    } else {
+                        String::new()
+                    };
+                    let label =
+                        format!("{number}{icon}{}{}", todo_icon(note), note.display_title());
+                    if ui
+                        .selectable_label(previewed == Some(note.id.as_str()), label)
+                        .clicked()
+                    {
+                        clicked = Some(note.clone());
+                    }
+                }
+            });
+        ui.separator();
+        clicked
     }
 
-    pub fn show(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, id: &str) {
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        ui: &mut egui::Ui,
+        id: &str,
+        mode: Mode,
+        viewer: &mut MarkdownViewer,
+    ) -> ListResponse {
         // Auto-select first item if nothing is selected
         if self.selected_item.is_none() && !self.items.is_empty() {
             self.selected_item = Some(0);
         }
 
-        // Handle j/k and arrow keys
-        if ctx.input(|i| i.key_pressed(egui::Key::J) || i.key_pressed(egui::Key::ArrowDown)) {
-            self.move_selection(Direction::Down);
-        }
-        if ctx.input(|i| i.key_pressed(egui::Key::K) || i.key_pressed(egui::Key::ArrowUp)) {
-            self.move_selection(Direction::Up);
+        // Keys belong to the editor while editing
+        if !self.is_editing() {
+            self.handle_keys(ctx, mode);
         }
 
         egui::SidePanel::left("note_preview")
@@ -83,9 +327,45 @@ impl SelectableList {
             .min_width(400.0)
             .show_inside(ui, |ui| {
                 egui::ScrollArea::vertical().show(ui, |ui| {
+                    let row_height = ui.spacing().interact_size.y + ui.spacing().item_spacing.y;
+                    self.page_size = (ui.clip_rect().height() / row_height) as usize;
+
+                    if let Some(note) = self.show_quick_notes(ui) {
+                        self.navigate_to(note);
+                    }
+
+                    let now = chrono::Utc::now();
                     for (i, item) in self.items.iter().enumerate() {
-                        let response =
-                            ui.selectable_value(&mut self.selected_item, Some(i), &item.title);
+                        let response = ui
+                            .horizontal(|ui| {
+                                let response = ui.selectable_value(
+                                    &mut self.selected_item,
+                                    Some(i),
+                                    format!("{}{}", todo_icon(item), item.display_title()),
+                                );
+                                ui.weak(relative_time(item.user_updated_time, now))
+                                    .on_hover_text(format!(
+                                        "Updated {}\nCreated {}",
+                                        format_local(item.user_updated_time),
+                                        format_local(item.user_created_time),
+                                    ));
+                                if let (true, Some(due)) = (item.is_open_todo(), item.todo_due) {
+                                    let text = format!("due {}", format_local(due));
+                                    if item.is_overdue(now) {
+                                        ui.colored_label(ui.visuals().error_fg_color, text);
+                                    } else {
+                                        ui.weak(text);
+                                    }
+                                }
+                                response
+                            })
+                            .inner;
+
+                        // Keep keyboard selection in view
+                        if self.scroll_to_selected && self.selected_item == Some(i) {
+                            response.scroll_to_me(Some(egui::Align::Center));
+                            self.scroll_to_selected = false;
+                        }
 
                         // Auto-scroll when selection changes
                         if response.clicked()
@@ -104,19 +384,91 @@ impl SelectableList {
                 });
             });
 
+        // Following links only makes sense for the note the links were followed from
+        if self.selected_item != self.navigation_origin {
+            self.clear_navigation();
+            self.navigation_origin = self.selected_item;
+        }
+
+        let mut response = ListResponse::default();
+        if let Some(editor) = &mut self.editor {
+            response.editor_action = editor.show(ctx, ui);
+            return response;
+        }
+
         egui::ScrollArea::vertical().id_salt(id).show(ui, |ui| {
-            if let Some(selected) = self.selected_item {
-                ui.heading(&self.items[selected].title);
+            if let Some(note) = self.previewed().cloned() {
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(!self.back_stack.is_empty(), egui::Button::new("◀"))
+                        .on_hover_text("Back (Alt+←)")
+                        .clicked()
+                    {
+                        self.navigate_back();
+                    }
+                    if ui
+                        .add_enabled(!self.forward_stack.is_empty(), egui::Button::new("▶"))
+                        .on_hover_text("Forward (Alt+→)")
+                        .clicked()
+                    {
+                        self.navigate_forward();
+                    }
+                    if ui.button("✏").on_hover_text("Edit (e)").clicked() {
+                        response.edit_requested = true;
+                    }
+                    if note.is_todo {
+                        let mut completed = note.todo_completed.is_some();
+                        if ui
+                            .checkbox(&mut completed, "")
+                            .on_hover_text("Toggle completed (x)")
+                            .clicked()
+                        {
+                            response.toggle_completed = true;
+                        }
+                    }
+                    ui.heading(note.display_title());
+                });
                 ui.separator();
-                // ui.label(&self.items[selected].body);
-                // TODO needs to be scrollable
 
                 egui::ScrollArea::vertical().show(ui, |ui| {
-                    ui.markdown(&self.items[selected].body);
+                    if note.encrypted {
+                        ui.weak("This note is end-to-end encrypted. Decrypt it in Joplin to read it here.");
+                    } else {
+                        response.clicked_link = viewer.show(ui, &note.body);
+                    }
+
+                    if let Some((_, backlinks)) = &self.backlinks {
+                        if !backlinks.is_empty() {
+                            ui.separator();
+                            egui::CollapsingHeader::new(format!(
+                                "Linked from ({})",
+                                backlinks.len()
+                            ))
+                            .default_open(true)
+                            .show(ui, |ui| {
+                                for backlink in backlinks {
+                                    if ui.link(&backlink.title).clicked() {
+                                        response.clicked_link = Some(backlink.id.clone());
+                                    }
+                                }
+                            });
+                        }
+                    }
                 });
             } else {
                 ui.label("Select a note to preview");
             }
         });
+
+        response
+    }
+}
+
+/// Checkbox shown before the titles of to-dos
+fn todo_icon(note: &Note) -> &'static str {
+    match (note.is_todo, note.todo_completed.is_some()) {
+        (false, _) => "",
+        (true, false) => "☐ ",
+        (true, true) => "☑ ",
     }
 }
@@ -1,49 +1,237 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 #![allow(rustdoc::missing_crate_level_docs)] // it's an example
 
-mod bm25;
-mod cli;
-mod list;
-mod note;
-mod ui;
+use egui_chalsedony_note_selector::{
+    bm25, cli, dates, export, fixture, folder, history, note, open, pins, query, resource, sort,
+    tokenizer, ui,
+};
 
+use bm25::{FieldWeights, NoteIndex};
 use clap::Parser;
 use cli::Cli;
-use note::Note;
+use dates::{format_local, DateRange};
+use export::export;
+use fixture::Fixture;
+use folder::Folder;
+use history::History;
+use note::{Include, Note};
+use open::Opener;
+use ordered_float::OrderedFloat;
+use pins::Pins;
+use query::Query;
+use resource::Resource;
+use sort::{sort_notes, SortContext};
+use tokenizer::TokenizerConfig;
 
 fn main() -> eframe::Result {
     let cli = Cli::parse();
+    let include = Include {
+        trashed: cli.include_trashed,
+        conflicts: cli.include_conflicts,
+        encrypted: cli.include_encrypted,
+    };
+    let tokenizer = TokenizerConfig::from(cli.tokenizer);
 
     match cli.command {
-        Some(cli::Commands::List) => {
-            let mut notes = Note::load_all(&cli.database).expect("Unable to load database");
-            notes.reverse();
+        Some(cli::Commands::List { sort, dates }) => {
+            let range = DateRange::from(dates);
+            let mut notes =
+                Note::load_all(&cli.database, include).expect("Unable to load database");
+            notes.retain(|note| range.contains(note));
+            sort_notes(&mut notes, sort, &load_sort_context(&cli.database, sort));
             for n in notes {
                 println!("{}\t {}", n.id, n.title);
             }
 
             // TODO: Implement list functionality using cli.database
         }
-        Some(cli::Commands::Search { query }) => {
+        Some(cli::Commands::Search { query, sort, dates }) => {
             println!("Searching in database: {}", cli.database);
             println!("Query: {}", query);
-            // TODO: Implement search functionality using cli.database
+            let query = Query::parse(&query).unwrap_or_else(|e| {
+                eprintln!("Invalid query: {e}");
+                std::process::exit(1);
+            });
+            let range = DateRange::from(dates);
             let mut notes =
-                Note::search(&cli.database, query.as_str()).expect("Unable to load database");
-            notes.reverse();
+                Note::find(&cli.database, &query, include).expect("Unable to load database");
+            notes.retain(|note| range.contains(note));
+            let weights = FieldWeights {
+                title: cli.title_weight,
+                body: cli.body_weight,
+            };
+            let index = NoteIndex::load(&cli.database, include, tokenizer.build())
+                .expect("Unable to load database");
+            query.rank(&mut notes, &index, weights, |note| {
+                OrderedFloat(note.user_updated_time.timestamp_millis() as f64)
+            });
+            sort_notes(&mut notes, sort, &load_sort_context(&cli.database, sort));
             for n in notes {
                 println!("{}\t {}", n.id, n.title);
             }
         }
         Some(cli::Commands::Preview { id }) => {
-            let body = Note::get_body_by_id(&cli.database, id.as_str()).expect("Unable to load database");
+            let body =
+                Note::get_body_by_id(&cli.database, id.as_str()).expect("Unable to load database");
             println!("{body}");
         }
+        Some(cli::Commands::Links { id }) => {
+            let notes = Note::links(&cli.database, id.as_str()).expect("Unable to load database");
+            for n in notes {
+                println!("{}\t {}", n.id, n.title);
+            }
+            let resources =
+                Resource::linked_from(&cli.database, id.as_str()).expect("Unable to load database");
+            for r in resources {
+                println!("{}\t {}", r.id, r.title);
+            }
+        }
+        Some(cli::Commands::Backlinks { id }) => {
+            let notes = Note::backlinks(&cli.database, id.as_str(), include)
+                .expect("Unable to load database");
+            for n in notes {
+                println!("{}\t {}", n.id, n.title);
+            }
+        }
+        Some(cli::Commands::Todos { all, overdue }) => {
+            let now = chrono::Utc::now();
+            let mut todos =
+                Note::load_all(&cli.database, include).expect("Unable to load database");
+            todos.retain(|note| {
+                note.is_todo && (all || note.is_open_todo()) && (!overdue || note.is_overdue(now))
+            });
+            // Open before completed, then by due time with undated to-dos last
+            todos
+                .sort_by_key(|note| (!note.is_open_todo(), note.todo_due.is_none(), note.todo_due));
+            for n in todos {
+                let status = match (n.todo_completed, n.todo_due) {
+                    (Some(_), _) => "done".to_string(),
+                    (None, Some(due)) if n.is_overdue(now) => {
+                        format!("overdue {}", format_local(due))
+                    }
+                    (None, Some(due)) => format!("due {}", format_local(due)),
+                    (None, None) => String::new(),
+                };
+                println!("{}\t {}\t {}", n.id, n.title, status);
+            }
+        }
+        Some(cli::Commands::Export { dir, ids, query }) => {
+            let notes = match query {
+                Some(query) => {
+                    let query = Query::parse(&query).unwrap_or_else(|e| {
+                        eprintln!("Invalid query: {e}");
+                        std::process::exit(1);
+                    });
+                    Note::find(&cli.database, &query, include).expect("Unable to load database")
+                }
+                None if ids.is_empty() => {
+                    eprintln!("Give the ids of the notes to export, or --query");
+                    std::process::exit(1);
+                }
+                None => ids
+                    .iter()
+                    .map(|id| Note::get_by_id(&cli.database, id))
+                    .collect::<Result<Vec<_>, _>>()
+                    .expect("Unable to load note"),
+            };
+            let summary = export(&cli.database, &notes, &dir).expect("Unable to export notes");
+            for id in &summary.missing_resources {
+                eprintln!("Resource file not found: {id}");
+            }
+            println!(
+                "Exported {} notes and {} resources to {}",
+                summary.notes,
+                summary.resources,
+                dir.display()
+            );
+        }
+        Some(cli::Commands::Create { title, body }) => {
+            if !cli.allow_write {
+                eprintln!("Refusing to create a note without --allow-write");
+                std::process::exit(1);
+            }
+            let folder = Folder::resolve(&cli.database, cli.notebook.as_deref())
+                .expect("Unable to load database")
+                .unwrap_or_else(|| {
+                    eprintln!("No notebook to create the note in");
+                    std::process::exit(1);
+                });
+            let note = Note::create(&cli.database, &title, &body, &folder.id)
+                .expect("Unable to create note");
+            println!("{}", note.markdown_link());
+        }
+        Some(cli::Commands::GenFixture { notes, seed }) => {
+            let path = std::path::Path::new(&cli.database);
+            if path.exists() {
+                eprintln!("Refusing to overwrite {}", path.display());
+                std::process::exit(1);
+            }
+            Fixture::create(path, seed)
+                .and_then(|mut fixture| fixture.generate(notes))
+                .expect("Unable to generate fixture database");
+            println!("Wrote {notes} notes to {}", path.display());
+        }
         None => {
             // No command provided, run the GUI
-            ui::run(cli.database)?
+            let options = ui::UiOptions {
+                opener: Opener::new(cli.joplin_opener, cli.editor),
+                allow_write: cli.allow_write,
+                include,
+                export_dir: cli.export_dir,
+                notebook: cli.notebook,
+                history: cli
+                    .history_file
+                    .or_else(History::default_path)
+                    .and_then(|path| History::load(&path).ok())
+                    .unwrap_or_default(),
+                pins: cli
+                    .pins_file
+                    .or_else(Pins::default_path)
+                    .and_then(|path| Pins::load(&path).ok())
+                    .unwrap_or_default(),
+                weights: FieldWeights {
+                    title: cli.title_weight,
+                    body: cli.body_weight,
+                },
+                tokenizer,
+                fresh: cli.fresh,
+                start: start_session(cli.query, cli.body_query, cli.select_id),
+                select_1: cli.select_1,
+                exit_0: cli.exit_0,
+            };
+            ui::run(cli.database, options)?
         }
     }
 
     Ok(())
 }
+
+/// Filters and selection given on the command line, None to restore the last session
+fn start_session(
+    query: Option<String>,
+    body_query: Option<String>,
+    selected_id: Option<String>,
+) -> Option<ui::Session> {
+    if query.is_none() && body_query.is_none() && selected_id.is_none() {
+        return None;
+    }
+    let query = query
+        .into_iter()
+        .chain(body_query.as_deref().map(query::body_term))
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(ui::Session {
+        query,
+        selected_id,
+        ..Default::default()
+    })
+}
+
+/// Data for sorting, only read from the database when the sort mode needs it
+fn load_sort_context(database: &str, sort: sort::SortMode) -> SortContext {
+    if sort.needs_context() {
+        SortContext::load(database).expect("Unable to load database")
+    } else {
+        SortContext::default()
+    }
+}
@@ -0,0 +1,624 @@
+use crate::links::is_id;
+use crate::resource::Resource;
+use eframe::egui;
+use egui_extras::syntax_highlighting::{code_view_ui, CodeTheme};
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// Inline formatting applied to a run of text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpanStyle {
+    pub strong: bool,
+    pub emphasis: bool,
+    pub strikethrough: bool,
+    pub code: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Span {
+    Text {
+        text: String,
+        style: SpanStyle,
+        link: Option<String>,
+    },
+    Image {
+        url: String,
+        alt: String,
+    },
+    LineBreak,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItem {
+    /// `Some` for task list items, holding whether the box is ticked
+    pub checked: Option<bool>,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Paragraph(Vec<Span>),
+    Heading(u8, Vec<Span>),
+    Code {
+        language: String,
+        code: String,
+    },
+    Quote(Vec<Block>),
+    List {
+        start: Option<u64>,
+        items: Vec<ListItem>,
+    },
+    Table {
+        header: Vec<Vec<Span>>,
+        rows: Vec<Vec<Vec<Span>>>,
+    },
+    Rule,
+}
+
+/// A block that is still receiving children while parsing
+enum Container {
+    Root(Vec<Block>),
+    Quote(Vec<Block>),
+    List {
+        start: Option<u64>,
+        items: Vec<ListItem>,
+    },
+    Item(ListItem),
+    Table {
+        header: Vec<Vec<Span>>,
+        rows: Vec<Vec<Vec<Span>>>,
+        row: Vec<Vec<Span>>,
+    },
+}
+
+/// Parse CommonMark (with GFM tables, task lists and strikethrough) into blocks
+pub fn parse(markdown: &str) -> Vec<Block> {
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH;
+
+    let mut stack = vec![Container::Root(Vec::new())];
+    let mut inline: Vec<Span> = Vec::new();
+    let mut style = SpanStyle::default();
+    let mut links: Vec<String> = Vec::new();
+    let mut image: Option<(String, String)> = None;
+    let mut code_block: Option<(String, String)> = None;
+
+    for event in Parser::new_ext(markdown, options) {
+        match event {
+            Event::Start(Tag::Paragraph) => flush_paragraph(&mut stack, &mut inline),
+            Event::End(TagEnd::Paragraph) => flush_paragraph(&mut stack, &mut inline),
+            Event::Start(Tag::Heading { .. }) => flush_paragraph(&mut stack, &mut inline),
+            Event::End(TagEnd::Heading(level)) => {
+                let spans = std::mem::take(&mut inline);
+                push_block(&mut stack, Block::Heading(heading_level(level), spans));
+            }
+            Event::Start(Tag::BlockQuote(_)) => {
+                flush_paragraph(&mut stack, &mut inline);
+                stack.push(Container::Quote(Vec::new()));
+            }
+            Event::End(TagEnd::BlockQuote(_)) => {
+                flush_paragraph(&mut stack, &mut inline);
+                if let Some(Container::Quote(blocks)) = stack.pop() {
+                    push_block(&mut stack, Block::Quote(blocks));
+                }
+            }
+            Event::Start(Tag::List(start)) => {
+                flush_paragraph(&mut stack, &mut inline);
+                stack.push(Container::List {
+                    start,
+                    items: Vec::new(),
+                });
+            }
+            Event::End(TagEnd::List(_)) => {
+                if let Some(Container::List { start, items }) = stack.pop() {
+                    push_block(&mut stack, Block::List { start, items });
+                }
+            }
+            Event::Start(Tag::Item) => stack.push(Container::Item(ListItem {
+                checked: None,
+                blocks: Vec::new(),
+            })),
+            Event::End(TagEnd::Item) => {
+                // Tight lists have no paragraph events around their text
+                flush_paragraph(&mut stack, &mut inline);
+                if let Some(Container::Item(item)) = stack.pop() {
+                    if let Some(Container::List { items, .. }) = stack.last_mut() {
+                        items.push(item);
+                    }
+                }
+            }
+            Event::TaskListMarker(checked) => {
+                if let Some(Container::Item(item)) = stack.last_mut() {
+                    item.checked = Some(checked);
+                }
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                flush_paragraph(&mut stack, &mut inline);
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => {
+                        info.split_whitespace().next().unwrap_or("").to_string()
+                    }
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code_block = Some((language, String::new()));
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((language, code)) = code_block.take() {
+                    let code = code.trim_end_matches('\n').to_string();
+                    push_block(&mut stack, Block::Code { language, code });
+                }
+            }
+            Event::Start(Tag::Table(_)) => {
+                flush_paragraph(&mut stack, &mut inline);
+                stack.push(Container::Table {
+                    header: Vec::new(),
+                    rows: Vec::new(),
+                    row: Vec::new(),
+                });
+            }
+            Event::End(TagEnd::TableCell) => {
+                if let Some(Container::Table { row, .. }) = stack.last_mut() {
+                    row.push(std::mem::take(&mut inline));
+                }
+            }
+            Event::End(TagEnd::TableHead) => {
+                if let Some(Container::Table { header, row, .. }) = stack.last_mut() {
+                    *header = std::mem::take(row);
+                }
+            }
+            Event::End(TagEnd::TableRow) => {
+                if let Some(Container::Table { rows, row, .. }) = stack.last_mut() {
+                    rows.push(std::mem::take(row));
+                }
+            }
+            Event::End(TagEnd::Table) => {
+                if let Some(Container::Table { header, rows, .. }) = stack.pop() {
+                    push_block(&mut stack, Block::Table { header, rows });
+                }
+            }
+            Event::Start(Tag::Emphasis) => style.emphasis = true,
+            Event::End(TagEnd::Emphasis) => style.emphasis = false,
+            Event::Start(Tag::Strong) => style.strong = true,
+            Event::End(TagEnd::Strong) => style.strong = false,
+            Event::Start(Tag::Strikethrough) => style.strikethrough = true,
+            Event::End(TagEnd::Strikethrough) => style.strikethrough = false,
+            Event::Start(Tag::Link { dest_url, .. }) => links.push(dest_url.to_string()),
+            Event::End(TagEnd::Link) => {
+                links.pop();
+            }
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                image = Some((dest_url.to_string(), String::new()));
+            }
+            Event::End(TagEnd::Image) => {
+                if let Some((url, alt)) = image.take() {
+                    inline.push(Span::Image { url, alt });
+                }
+            }
+            Event::Text(text) => {
+                if let Some((_, code)) = code_block.as_mut() {
+                    code.push_str(&text);
+                } else if let Some((_, alt)) = image.as_mut() {
+                    alt.push_str(&text);
+                } else {
+                    push_text(&mut inline, &text, style, links.last());
+                }
+            }
+            Event::Code(text) => {
+                let style = SpanStyle {
+                    code: true,
+                    ..style
+                };
+                push_text(&mut inline, &text, style, links.last());
+            }
+            Event::Html(html) | Event::InlineHtml(html) => {
+                // Joplin writes resized images as `<img src=":/id" width="..."/>`
+                match html_img_src(&html) {
+                    Some(url) => inline.push(Span::Image {
+                        url,
+                        alt: String::new(),
+                    }),
+                    None => push_text(&mut inline, html.trim_end(), style, links.last()),
+                }
+            }
+            Event::SoftBreak => push_text(&mut inline, " ", style, links.last()),
+            Event::HardBreak => inline.push(Span::LineBreak),
+            Event::Rule => {
+                flush_paragraph(&mut stack, &mut inline);
+                push_block(&mut stack, Block::Rule);
+            }
+            _ => {}
+        }
+    }
+
+    flush_paragraph(&mut stack, &mut inline);
+    // Unwind anything left open by malformed input
+    while stack.len() > 1 {
+        match stack.pop() {
+            Some(Container::Quote(blocks)) => push_block(&mut stack, Block::Quote(blocks)),
+            Some(Container::Item(item)) => push_block(
+                &mut stack,
+                Block::List {
+                    start: None,
+                    items: vec![item],
+                },
+            ),
+            _ => {}
+        }
+    }
+    match stack.pop() {
+        Some(Container::Root(blocks)) => blocks,
+        _ => Vec::new(),
+    }
+}
+
+fn heading_level(level: HeadingLevel) -> u8 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+fn push_text(inline: &mut Vec<Span>, text: &str, style: SpanStyle, link: Option<&String>) {
+    // Merge with the previous run when nothing changed, keeps the span count low
+    if let Some(Span::Text {
+        text: prev,
+        style: prev_style,
+        link: prev_link,
+    }) = inline.last_mut()
+    {
+        if *prev_style == style && prev_link.as_ref() == link {
+            prev.push_str(text);
+            return;
+        }
+    }
+    inline.push(Span::Text {
+        text: text.to_string(),
+        style,
+        link: link.cloned(),
+    });
+}
+
+fn push_block(stack: &mut [Container], block: Block) {
+    match stack.last_mut() {
+        Some(Container::Root(blocks)) | Some(Container::Quote(blocks)) => blocks.push(block),
+        Some(Container::Item(item)) => item.blocks.push(block),
+        _ => {}
+    }
+}
+
+fn flush_paragraph(stack: &mut [Container], inline: &mut Vec<Span>) {
+    if !inline.is_empty() {
+        push_block(stack, Block::Paragraph(std::mem::take(inline)));
+    }
+}
+
+/// Extract the `src` attribute of an `<img>` tag
+fn html_img_src(html: &str) -> Option<String> {
+    let html = html.trim();
+    if !html.starts_with("<img") {
+        return None;
+    }
+    let start = html.find("src=")? + "src=".len();
+    let rest = &html[start..];
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let rest = &rest[1..];
+    let end = rest.find(quote)?;
+    Some(rest[..end].to_string())
+}
+
+/// The id a `:/id` Joplin link points at, if it is one
+pub fn joplin_id(url: &str) -> Option<&str> {
+    let id = url.strip_prefix(":/")?;
+    // Links may carry a heading anchor, e.g. `:/id#section`
+    let id = id.split('#').next().unwrap_or(id);
+    is_id(id).then_some(id)
+}
+
+/// Renders note bodies, resolving Joplin resources against a database
+pub struct MarkdownViewer {
+    database: String,
+    /// Image URI for each resource id seen so far, `None` if it can't be shown
+    resources: HashMap<String, Option<String>>,
+    /// Hash of the last rendered source and its parsed blocks
+    parsed: Option<(u64, Vec<Block>)>,
+    /// Id of a `:/id` link clicked during the current frame
+    clicked: Option<String>,
+}
+
+impl MarkdownViewer {
+    pub fn new(database: &str) -> Self {
+        Self {
+            database: database.to_string(),
+            resources: HashMap::new(),
+            parsed: None,
+            clicked: None,
+        }
+    }
+
+    /// Render `markdown`, returning the id of any `:/id` link that was clicked
+    pub fn show(&mut self, ui: &mut egui::Ui, markdown: &str) -> Option<String> {
+        let mut hasher = DefaultHasher::new();
+        markdown.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let blocks = match self.parsed.take() {
+            Some((h, blocks)) if h == hash => blocks,
+            _ => parse(markdown),
+        };
+        let mut table_count = 0;
+        self.show_blocks(ui, &blocks, &mut table_count);
+        self.parsed = Some((hash, blocks));
+        self.clicked.take()
+    }
+
+    fn show_blocks(&mut self, ui: &mut egui::Ui, blocks: &[Block], table_count: &mut usize) {
+        for block in blocks {
+            self.show_block(ui, block, table_count);
+        }
+    }
+
+    fn show_block(&mut self, ui: &mut egui::Ui, block: &Block, table_count: &mut usize) {
+        match block {
+            Block::Paragraph(spans) => self.show_spans(ui, spans, None, false),
+            Block::Heading(level, spans) => {
+                let body_size = egui::TextStyle::Body.resolve(ui.style()).size;
+                let scale = match level {
+                    1 => 1.8,
+                    2 => 1.5,
+                    3 => 1.3,
+                    4 => 1.15,
+                    _ => 1.0,
+                };
+                ui.add_space(4.0);
+                self.show_spans(ui, spans, Some(body_size * scale), true);
+            }
+            Block::Code { language, code } => {
+                let theme = CodeTheme::from_memory(ui.ctx(), ui.style());
+                egui::Frame::group(ui.style()).show(ui, |ui| {
+                    ui.set_width(ui.available_width());
+                    code_view_ui(ui, &theme, code, language);
+                });
+            }
+            Block::Quote(blocks) => {
+                ui.horizontal_top(|ui| {
+                    let (rect, _) =
+                        ui.allocate_exact_size(egui::vec2(4.0, 0.0), egui::Sense::hover());
+                    ui.vertical(|ui| self.show_blocks(ui, blocks, table_count));
+                    let bar = egui::Rect::from_min_max(
+                        rect.min,
+                        egui::pos2(rect.max.x, ui.min_rect().max.y),
+                    );
+                    ui.painter()
+                        .rect_filled(bar, 0.0, ui.visuals().weak_text_color());
+                });
+            }
+            Block::List { start, items } => {
+                for (i, item) in items.iter().enumerate() {
+                    ui.horizontal_top(|ui| {
+                        ui.add_space(8.0);
+                        match (item.checked, start) {
+                            (Some(checked), _) => {
+                                let mut checked = checked;
+                                ui.add_enabled(false, egui::Checkbox::without_text(&mut checked));
+                            }
+                            (None, Some(start)) => {
+                                ui.label(format!("{}.", start + i as u64));
+                            }
+                            (None, None) => {
+                                ui.label("•");
+                            }
+                        }
+                        ui.vertical(|ui| self.show_blocks(ui, &item.blocks, table_count));
+                    });
+                }
+            }
+            Block::Table { header, rows } => {
+                *table_count += 1;
+                egui::Frame::group(ui.style()).show(ui, |ui| {
+                    egui::Grid::new(ui.id().with(("markdown_table", *table_count)))
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for cell in header {
+                                self.show_spans(ui, cell, None, true);
+                            }
+                            ui.end_row();
+                            for row in rows {
+                                for cell in row {
+                                    self.show_spans(ui, cell, None, false);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+            }
+            Block::Rule => {
+                ui.separator();
+            }
+        }
+    }
+
+    fn show_spans(&mut self, ui: &mut egui::Ui, spans: &[Span], size: Option<f32>, strong: bool) {
+        ui.horizontal_wrapped(|ui| {
+            ui.spacing_mut().item_spacing.x = 0.0;
+            for span in spans {
+                match span {
+                    Span::Text { text, style, link } => {
+                        let mut rich = egui::RichText::new(text);
+                        if let Some(size) = size {
+                            rich = rich.size(size);
+                        }
+                        if style.strong || strong {
+                            rich = rich.strong();
+                        }
+                        if style.emphasis {
+                            rich = rich.italics();
+                        }
+                        if style.strikethrough {
+                            rich = rich.strikethrough();
+                        }
+                        if style.code {
+                            rich = rich.code();
+                        }
+                        match link {
+                            Some(url) => match joplin_id(url) {
+                                Some(id) => {
+                                    if ui.link(rich).clicked() {
+                                        self.clicked = Some(id.to_string());
+                                    }
+                                }
+                                None => {
+                                    ui.hyperlink_to(rich, url);
+                                }
+                            },
+                            None => {
+                                ui.label(rich);
+                            }
+                        }
+                    }
+                    Span::Image { url, alt } => self.show_image(ui, url, alt),
+                    Span::LineBreak => ui.end_row(),
+                }
+            }
+        });
+    }
+
+    fn show_image(&mut self, ui: &mut egui::Ui, url: &str, alt: &str) {
+        let uri = match joplin_id(url) {
+            Some(id) => self.resource_uri(id),
+            None if url.starts_with("file://") => Some(url.to_string()),
+            None => None,
+        };
+        match uri {
+            Some(uri) => {
+                ui.add(egui::Image::new(uri).max_width(ui.available_width()));
+            }
+            None => {
+                let text = if alt.is_empty() { url } else { alt };
+                ui.hyperlink_to(format!("🖼 {text}"), url);
+            }
+        }
+    }
+
+    /// `file://` URI of an image resource, looked up once and cached
+    fn resource_uri(&mut self, id: &str) -> Option<String> {
+        if let Some(uri) = self.resources.get(id) {
+            return uri.clone();
+        }
+        let uri = Resource::get_by_id(&self.database, id)
+            .ok()
+            .filter(Resource::is_image)
+            .and_then(|resource| std::path::absolute(resource.path(&self.database)).ok())
+            .map(|path| format!("file://{}", path.display()));
+        self.resources.insert(id.to_string(), uri.clone());
+        uri
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Span {
+        Span::Text {
+            text: s.to_string(),
+            style: SpanStyle::default(),
+            link: None,
+        }
+    }
+
+    #[test]
+    fn test_paragraph_and_heading() {
+        let blocks = parse("# Title\n\nSome *text*");
+        assert_eq!(blocks[0], Block::Heading(1, vec![text("Title")]));
+        assert_eq!(
+            blocks[1],
+            Block::Paragraph(vec![
+                text("Some "),
+                Span::Text {
+                    text: "text".to_string(),
+                    style: SpanStyle {
+                        emphasis: true,
+                        ..Default::default()
+                    },
+                    link: None,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_fenced_code_block_language() {
+        let blocks = parse("```rust\nfn main() {}\n```");
+        assert_eq!(
+            blocks,
+            vec![Block::Code {
+                language: "rust".to_string(),
+                code: "fn main() {}".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_task_list() {
+        let blocks = parse("- [x] done\n- [ ] open\n- plain");
+        let Block::List { start, items } = &blocks[0] else {
+            panic!("expected a list, got {blocks:?}");
+        };
+        assert_eq!(*start, None);
+        let checked: Vec<_> = items.iter().map(|item| item.checked).collect();
+        assert_eq!(checked, vec![Some(true), Some(false), None]);
+        assert_eq!(items[1].blocks, vec![Block::Paragraph(vec![text("open")])]);
+    }
+
+    #[test]
+    fn test_table() {
+        let blocks = parse("| a | b |\n|---|---|\n| 1 | 2 |\n| 3 | 4 |");
+        let Block::Table { header, rows, .. } = &blocks[0] else {
+            panic!("expected a table, got {blocks:?}");
+        };
+        assert_eq!(header, &vec![vec![text("a")], vec![text("b")]]);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1], vec![vec![text("3")], vec![text("4")]]);
+    }
+
+    #[test]
+    fn test_joplin_image() {
+        let blocks = parse("![diagram](:/0123456789abcdef0123456789abcdef)");
+        assert_eq!(
+            blocks,
+            vec![Block::Paragraph(vec![Span::Image {
+                url: ":/0123456789abcdef0123456789abcdef".to_string(),
+                alt: "diagram".to_string(),
+            }])]
+        );
+    }
+
+    #[test]
+    fn test_html_image() {
+        let blocks = parse("<img src=\":/abc\" width=\"200\"/>");
+        assert_eq!(
+            blocks,
+            vec![Block::Paragraph(vec![Span::Image {
+                url: ":/abc".to_string(),
+                alt: String::new(),
+            }])]
+        );
+    }
+
+    #[test]
+    fn test_joplin_id() {
+        const ID: &str = "0123456789abcdef0123456789abcdef";
+        assert_eq!(joplin_id(&format!(":/{ID}")), Some(ID));
+        assert_eq!(joplin_id(&format!(":/{ID}#heading")), Some(ID));
+        assert_eq!(joplin_id("https://example.com"), None);
+        assert_eq!(joplin_id(":/"), None);
+        // Only whole Joplin ids, as `links` finds them
+        assert_eq!(joplin_id(":/foo"), None);
+        assert_eq!(joplin_id(&format!(":/{}", ID.to_uppercase())), None);
+    }
+}
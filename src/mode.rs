@@ -0,0 +1,56 @@
+use eframe::egui;
+
+/// Vim-style input mode of the picker.
+///
+/// The mode is derived from keyboard focus each frame: while a text field (a
+/// filter or the note editor) has focus the picker is in [`Mode::Insert`] and
+/// letters are typed into the field, otherwise it is in [`Mode::Normal`] and
+/// letters drive the list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    #[default]
+    Insert,
+    Normal,
+}
+
+impl Mode {
+    /// Insert mode if any of the given text edits currently has focus
+    pub fn from_focus(ctx: &egui::Context, text_edit_ids: &[&str]) -> Self {
+        let focused = ctx.memory(|mem| mem.focused());
+        let editing = text_edit_ids
+            .iter()
+            .any(|id| focused == Some(egui::Id::new(*id)));
+        if editing {
+            Mode::Insert
+        } else {
+            Mode::Normal
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Mode::Insert => "-- INSERT --",
+            Mode::Normal => "-- NORMAL --",
+        }
+    }
+}
+
+/// Consume a key press together with the text it would have typed.
+///
+/// Used when a normal mode key moves focus into a text edit, so the key
+/// itself doesn't end up in the field on the same frame.
+pub fn consume_key_and_text(
+    ctx: &egui::Context,
+    modifiers: egui::Modifiers,
+    key: egui::Key,
+    text: &str,
+) -> bool {
+    ctx.input_mut(|i| {
+        let pressed = i.consume_key(modifiers, key);
+        if pressed {
+            i.events
+                .retain(|e| !matches!(e, egui::Event::Text(t) if t == text));
+        }
+        pressed
+    })
+}
@@ -1,25 +1,149 @@
-use rusqlite::{Connection, Result as SqlResult};
+use crate::dates::{from_millis, optional_from_millis};
+use crate::links::{is_id, linked_ids};
+use crate::query::Query;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use rusqlite::{Connection, OpenFlags, OptionalExtension, Result as SqlResult};
 use std::fmt::Display;
 
+/// Recorded in `source_application` of notes created by this program
+pub const SOURCE_APPLICATION: &str = "net.ryangreenup.chalsedony_selector";
+
 #[derive(Debug, Clone)]
 pub struct Note {
     pub title: String,
     pub body: String,
     pub id: String,
+    /// Id of the notebook the note is in
+    pub parent_id: String,
+    /// Changes on every save and is used to detect edit conflicts
+    pub updated_time: DateTime<Utc>,
+    /// Creation time shown to the user
+    pub user_created_time: DateTime<Utc>,
+    /// Modification time shown to the user
+    pub user_updated_time: DateTime<Utc>,
+    /// A to-do rather than a plain note
+    pub is_todo: bool,
+    /// When the to-do was checked off, `None` while it is open
+    pub todo_completed: Option<DateTime<Utc>>,
+    /// When the to-do is due, if it has an alarm
+    pub todo_due: Option<DateTime<Utc>>,
+    /// End-to-end encrypted and not decrypted yet, the title and body are not readable
+    pub encrypted: bool,
+}
+
+/// Notes that are hidden unless asked for
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Include {
+    /// Notes in Joplin's trash
+    pub trashed: bool,
+    /// Copies Joplin keeps of notes that were changed on two devices at once
+    pub conflicts: bool,
+    /// Notes still encrypted, see [`Note::encrypted`]
+    pub encrypted: bool,
+}
+
+impl Include {
+    /// Condition hiding the notes not included.
+    ///
+    /// Databases from before Joplin had a trash have no `deleted_time`, the
+    /// columns are only used when present.
+    fn sql(&self, conn: &Connection) -> SqlResult<String> {
+        let mut conditions = Vec::new();
+        for (included, column, condition) in [
+            (self.trashed, "deleted_time", "notes.deleted_time = 0"),
+            (self.conflicts, "is_conflict", "notes.is_conflict = 0"),
+            (
+                self.encrypted,
+                "encryption_applied",
+                "notes.encryption_applied = 0",
+            ),
+        ] {
+            if !included && column_exists(conn, "notes", column)? {
+                conditions.push(condition);
+            }
+        }
+        if conditions.is_empty() {
+            Ok("1".to_string())
+        } else {
+            Ok(conditions.join(" AND "))
+        }
+    }
 }
 
+/// Columns read for a [`Note`], in the order [`Note::from_row`] expects
+const COLUMNS: &str = "notes.title, notes.body, notes.id, notes.parent_id, notes.updated_time,
+                       notes.user_created_time, notes.user_updated_time,
+                       notes.is_todo, notes.todo_completed, notes.todo_due,
+                       notes.encryption_applied";
+
 impl Note {
-    pub fn load_all(db_path: &str) -> SqlResult<Vec<Note>> {
-        let conn = Connection::open(db_path)?;
-
-        let mut stmt = conn.prepare("SELECT title, body, id FROM notes ORDER BY user_updated_time")?;
-        let note_iter = stmt.query_map([], |row| {
-            Ok(Note {
-                title: row.get(0)?,
-                body: row.get(1)?,
-                id: row.get(2)?,
-            })
-        })?;
+    fn from_row(row: &rusqlite::Row) -> SqlResult<Note> {
+        Ok(Note {
+            title: row.get(0)?,
+            body: row.get(1)?,
+            id: row.get(2)?,
+            parent_id: row.get(3)?,
+            updated_time: from_millis(row.get(4)?),
+            user_created_time: from_millis(row.get(5)?),
+            user_updated_time: from_millis(row.get(6)?),
+            is_todo: row.get(7)?,
+            todo_completed: optional_from_millis(row.get(8)?),
+            todo_due: optional_from_millis(row.get(9)?),
+            encrypted: row.get(10)?,
+        })
+    }
+
+    /// A plain note with only an id and a title, for tests to build on
+    #[cfg(test)]
+    pub fn for_test(id: &str, title: &str) -> Note {
+        Note {
+            title: title.to_string(),
+            body: String::new(),
+            id: id.to_string(),
+            parent_id: String::new(),
+            updated_time: from_millis(0),
+            user_created_time: from_millis(0),
+            user_updated_time: from_millis(0),
+            is_todo: false,
+            todo_completed: None,
+            todo_due: None,
+            encrypted: false,
+        }
+    }
+
+    /// A to-do that hasn't been completed
+    pub fn is_open_todo(&self) -> bool {
+        self.is_todo && self.todo_completed.is_none()
+    }
+
+    /// An open to-do whose due time has passed
+    pub fn is_overdue(&self, now: DateTime<Utc>) -> bool {
+        self.is_open_todo() && self.todo_due.is_some_and(|due| due < now)
+    }
+
+    /// Title to show, encrypted notes have no readable one
+    pub fn display_title(&self) -> &str {
+        if self.encrypted {
+            "🔒 Encrypted note"
+        } else {
+            &self.title
+        }
+    }
+
+    /// Markdown link to the note, as inserted into other documents
+    pub fn markdown_link(&self) -> String {
+        format!("[{}](:/{})", self.title, self.id)
+    }
+
+    pub fn load_all(db_path: &str, include: Include) -> SqlResult<Vec<Note>> {
+        let conn = open_read_only(db_path)?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM notes WHERE {} ORDER BY user_updated_time",
+            include.sql(&conn)?
+        ))?;
+        let note_iter = stmt.query_map([], Self::from_row)?;
 
         let mut notes = Vec::new();
         for note in note_iter {
@@ -29,25 +153,77 @@ impl Note {
         Ok(notes)
     }
 
-    pub fn search(db_path: &str, query: &str) -> SqlResult<Vec<Note>> {
-        let conn = Connection::open(db_path)?;
+    /// Notes matching the field conditions of a query, see [`Query::sql`]
+    pub fn find(db_path: &str, query: &Query, include: Include) -> SqlResult<Vec<Note>> {
+        let conn = open_read_only(db_path)?;
 
-        // Query using FTS5 table, ordering by BM25 score
-        let mut stmt = conn.prepare(
-            "SELECT notes.title, notes.body, notes.id
-             FROM notes
-             JOIN notes_fts5_porter ON notes.id = notes_fts5_porter.id
-             WHERE notes_fts5_porter MATCH ?1
-             ORDER BY bm25(notes_fts5_porter)",
-        )?;
+        let (condition, params) = query.sql(table_exists(&conn, "notes_fts5_porter")?);
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM notes WHERE {condition} AND {} ORDER BY user_updated_time",
+            include.sql(&conn)?
+        ))?;
+        let note_iter = stmt.query_map(rusqlite::params_from_iter(params), Self::from_row)?;
 
-        let note_iter = stmt.query_map([query], |row| {
-            Ok(Note {
-                title: row.get(0)?,
-                body: row.get(1)?,
-                id: row.get(2)?,
-            })
-        })?;
+        let mut notes = Vec::new();
+        for note in note_iter {
+            notes.push(note?);
+        }
+
+        Ok(notes)
+    }
+
+    pub fn get_by_id(db_path: &str, note_id: &str) -> SqlResult<Note> {
+        let conn = open_read_only(db_path)?;
+
+        let mut stmt = conn.prepare(&format!("SELECT {COLUMNS} FROM notes WHERE id = ?"))?;
+        stmt.query_row([note_id], Self::from_row)
+    }
+
+    /// Notes linked to from the given note's body, in the order the links appear
+    ///
+    /// Links to resources and to notes that no longer exist are skipped.
+    pub fn links(db_path: &str, note_id: &str) -> SqlResult<Vec<Note>> {
+        let conn = open_read_only(db_path)?;
+
+        let body: String =
+            conn.query_row("SELECT body FROM notes WHERE id = ?", [note_id], |row| {
+                row.get(0)
+            })?;
+        let mut stmt = conn.prepare(&format!("SELECT {COLUMNS} FROM notes WHERE id = ?"))?;
+
+        let mut notes = Vec::new();
+        for id in linked_ids(&body) {
+            let note = stmt.query_row([&id], Self::from_row).optional()?;
+            notes.extend(note);
+        }
+
+        Ok(notes)
+    }
+
+    /// Notes linking to the given note or resource.
+    ///
+    /// Bodies are scanned for `:/id` references, and for resources Joplin's
+    /// `note_resources` table is consulted too when the database has one.
+    pub fn backlinks(db_path: &str, note_id: &str, include: Include) -> SqlResult<Vec<Note>> {
+        // Anything shorter would match the start of every id it prefixes
+        if !is_id(note_id) {
+            return Ok(Vec::new());
+        }
+        let conn = open_read_only(db_path)?;
+
+        let note_resources = if table_exists(&conn, "note_resources")? {
+            "OR id IN (SELECT note_id FROM note_resources
+                       WHERE resource_id = ?1 AND is_associated = 1)"
+        } else {
+            ""
+        };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM notes
+             WHERE id != ?1 AND (instr(body, ':/' || ?1) > 0 {note_resources}) AND {}
+             ORDER BY title",
+            include.sql(&conn)?
+        ))?;
+        let note_iter = stmt.query_map([note_id], Self::from_row)?;
 
         let mut notes = Vec::new();
         for note in note_iter {
@@ -57,37 +233,380 @@ impl Note {
         Ok(notes)
     }
 
+    /// Insert a new, unencrypted note into the given notebook.
+    ///
+    /// The row is filled in the way Joplin itself creates notes, so it syncs
+    /// and shows up in the Joplin apps like any other note.
+    pub fn create(db_path: &str, title: &str, body: &str, parent_id: &str) -> SqlResult<Note> {
+        let conn = open_writable(db_path)?;
+
+        let id = new_id();
+        let now = chrono::Utc::now().timestamp_millis();
+        conn.execute(
+            "INSERT INTO notes (
+                id, parent_id, title, body,
+                created_time, updated_time, user_created_time, user_updated_time,
+                is_conflict, is_todo, todo_due, todo_completed,
+                source, source_application, application_data, markup_language,
+                encryption_cipher_text, encryption_applied, \"order\"
+             ) VALUES (
+                ?1, ?2, ?3, ?4,
+                ?5, ?5, ?5, ?5,
+                0, 0, 0, 0,
+                'chalsedony_selector', ?6, '', 1,
+                '', 0, ?5
+             )",
+            rusqlite::params![id, parent_id, title, body, now, SOURCE_APPLICATION],
+        )?;
+        record_item_change(&conn, &id, ItemChangeType::Create, now)?;
+
+        Ok(Note {
+            title: title.to_string(),
+            body: body.to_string(),
+            id,
+            parent_id: parent_id.to_string(),
+            updated_time: from_millis(now),
+            user_created_time: from_millis(now),
+            user_updated_time: from_millis(now),
+            is_todo: false,
+            todo_completed: None,
+            todo_due: None,
+            encrypted: false,
+        })
+    }
+
+    /// Write a new title and body back to the note.
+    ///
+    /// `updated_time` and `user_updated_time` are bumped, which is what Joplin's
+    /// sync compares against to find local changes. Unless `force` is set the
+    /// write only happens if the row is unchanged since `self` was loaded,
+    /// otherwise [`SaveOutcome::Conflict`] is returned and nothing is written.
+    pub fn save(
+        &self,
+        db_path: &str,
+        title: &str,
+        body: &str,
+        force: bool,
+    ) -> SqlResult<SaveOutcome> {
+        let mut conn = open_writable(db_path)?;
+        let tx = conn.transaction()?;
+
+        let current: Option<i64> = tx
+            .query_row(
+                "SELECT updated_time FROM notes WHERE id = ?",
+                [&self.id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match current {
+            None => return Ok(SaveOutcome::Deleted),
+            Some(current) if current != self.updated_time.timestamp_millis() && !force => {
+                return Ok(SaveOutcome::Conflict);
+            }
+            Some(_) => {}
+        }
+
+        // Strictly increase the timestamp so the change is never missed
+        let now = chrono::Utc::now()
+            .timestamp_millis()
+            .max(current.unwrap_or(0) + 1);
+        tx.execute(
+            "UPDATE notes
+             SET title = ?1, body = ?2, updated_time = ?3, user_updated_time = ?3
+             WHERE id = ?4",
+            rusqlite::params![title, body, now, self.id],
+        )?;
+        record_item_change(&tx, &self.id, ItemChangeType::Update, now)?;
+        tx.commit()?;
+
+        Ok(SaveOutcome::Saved(Note {
+            title: title.to_string(),
+            body: body.to_string(),
+            updated_time: from_millis(now),
+            user_updated_time: from_millis(now),
+            ..self.clone()
+        }))
+    }
+
+    /// Check off a to-do, or reopen it.
+    ///
+    /// The update times are bumped like in [`Note::save`]. There is no
+    /// conflict check as the change doesn't depend on the rest of the note.
+    pub fn set_completed(&self, db_path: &str, completed: bool) -> SqlResult<Note> {
+        let mut conn = open_writable(db_path)?;
+        let tx = conn.transaction()?;
+
+        let current: i64 = tx.query_row(
+            "SELECT updated_time FROM notes WHERE id = ?",
+            [&self.id],
+            |row| row.get(0),
+        )?;
+        let now = chrono::Utc::now().timestamp_millis().max(current + 1);
+        let todo_completed = if completed { now } else { 0 };
+        tx.execute(
+            "UPDATE notes
+             SET todo_completed = ?1, updated_time = ?2, user_updated_time = ?2
+             WHERE id = ?3",
+            rusqlite::params![todo_completed, now, self.id],
+        )?;
+        record_item_change(&tx, &self.id, ItemChangeType::Update, now)?;
+        tx.commit()?;
+
+        Ok(Note {
+            todo_completed: optional_from_millis(todo_completed),
+            updated_time: from_millis(now),
+            user_updated_time: from_millis(now),
+            ..self.clone()
+        })
+    }
+
     pub fn get_body_by_id(db_path: &str, note_id: &str) -> SqlResult<String> {
-        let conn = Connection::open(db_path)?;
-        
+        let conn = open_read_only(db_path)?;
+
         let mut stmt = conn.prepare("SELECT body FROM notes WHERE id = ?")?;
         let body: String = stmt.query_row([note_id], |row| row.get(0))?;
-        
+
         Ok(body)
     }
 }
 
+/// Result of [`Note::save`]
+#[derive(Debug)]
+pub enum SaveOutcome {
+    Saved(Note),
+    /// The note was modified elsewhere after it was loaded
+    Conflict,
+    /// The note no longer exists
+    Deleted,
+}
+
+/// Kinds of change recorded in Joplin's `item_changes` table
+#[derive(Debug, Clone, Copy)]
+enum ItemChangeType {
+    Create = 1,
+    Update = 2,
+}
+
+/// Log a change to a note in `item_changes`, when the database has that table.
+///
+/// Joplin feeds its search index and note history from this table, so notes
+/// written here are picked up the same way as edits made in Joplin.
+fn record_item_change(
+    conn: &Connection,
+    note_id: &str,
+    change: ItemChangeType,
+    time: i64,
+) -> SqlResult<()> {
+    const ITEM_TYPE_NOTE: i64 = 1;
+    const SOURCE_LOCAL: i64 = 1;
+
+    if table_exists(conn, "item_changes")? {
+        conn.execute(
+            "INSERT INTO item_changes (item_type, item_id, type, created_time, source, before_change_item)
+             VALUES (?1, ?2, ?3, ?4, ?5, '')",
+            rusqlite::params![ITEM_TYPE_NOTE, note_id, change as i64, time, SOURCE_LOCAL],
+        )?;
+    }
+    Ok(())
+}
+
+/// Open the database for reading only, so nothing can be modified by accident
+pub fn open_read_only(db_path: &str) -> SqlResult<Connection> {
+    Connection::open_with_flags(
+        db_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+}
+
+/// Open the database for writing, only used when `--allow-write` is given
+pub fn open_writable(db_path: &str) -> SqlResult<Connection> {
+    Connection::open_with_flags(
+        db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+}
+
+/// A new Joplin item id: a random (version 4) UUID written as 32 hex digits without dashes
+pub fn new_id() -> String {
+    random_id(&mut rand::thread_rng())
+}
+
+/// A Joplin item id drawn from `rng`, so seeded generators give the same ids
+pub fn random_id(rng: &mut impl Rng) -> String {
+    let mut bytes: [u8; 16] = rng.gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Whether the database has a table called `name`, Joplin's schema varies between versions
+pub fn table_exists(conn: &Connection, name: &str) -> SqlResult<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+        [name],
+        |row| row.get(0),
+    )
+}
+
+/// Whether a table has a column, e.g. one added by a later version of Joplin
+pub fn column_exists(conn: &Connection, table: &str, column: &str) -> SqlResult<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+        [table, column],
+        |row| row.get(0),
+    )
+}
+
 impl Display for Note {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.title)
     }
 }
 
-/*
-impl Note {
-    // This is useful for development
-    use rand::distributions::Alphanumeric;
-    use rand::{thread_rng, Rng};
-    pub fn random(title: &str, body: &str) -> Self {
-        let title = title.into();
-        let body = body.into();
-        let rng = thread_rng();
-        let id: String = rng
-            .sample_iter(&Alphanumeric)
-            .take(16)
-            .map(char::from)
-            .collect();
-        Self { title, body, id }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::Fixture;
+
+    #[test]
+    fn test_new_id_is_joplin_shaped() {
+        let id = new_id();
+        assert_eq!(id.len(), 32);
+        assert!(id
+            .chars()
+            .all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+        // Version 4 UUID
+        assert_eq!(&id[12..13], "4");
+        assert_ne!(new_id(), id);
+    }
+
+    #[test]
+    fn test_backlinks_need_a_whole_id() {
+        let mut fixture = Fixture::temporary().unwrap();
+        let folder = fixture.folder("Work", "").unwrap();
+        let target = fixture.note("Target", "", &folder).unwrap();
+        let body = format!("See [target](:/{target})");
+        fixture.note("Linking", &body, &folder).unwrap();
+
+        let backlinks = Note::backlinks(fixture.path(), &target, Include::default()).unwrap();
+        assert_eq!(backlinks.len(), 1);
+        assert_eq!(backlinks[0].title, "Linking");
+        for prefix in ["", &target[..1], &target[..31]] {
+            let backlinks = Note::backlinks(fixture.path(), prefix, Include::default()).unwrap();
+            assert!(backlinks.is_empty(), "{prefix:?}");
+        }
+    }
+
+    #[test]
+    fn test_save_detects_conflicts() {
+        let mut fixture = Fixture::temporary().unwrap();
+        let id = fixture.note("Title", "Body", "f").unwrap();
+        let db_path = fixture.path();
+
+        let loaded = Note::get_by_id(db_path, &id).unwrap();
+        let SaveOutcome::Saved(saved) = loaded.save(db_path, "New", "Text", false).unwrap() else {
+            panic!("first save should succeed");
+        };
+        assert!(saved.updated_time > loaded.updated_time);
+        assert_eq!(Note::get_by_id(db_path, &id).unwrap().title, "New");
+
+        // `loaded` is now stale
+        let outcome = loaded.save(db_path, "Other", "Text", false).unwrap();
+        assert!(matches!(outcome, SaveOutcome::Conflict));
+        let outcome = loaded.save(db_path, "Other", "Text", true).unwrap();
+        assert!(matches!(outcome, SaveOutcome::Saved(_)));
+    }
+
+    #[test]
+    fn test_find_applies_conditions() {
+        let mut fixture = Fixture::temporary().unwrap();
+        let work = fixture.folder("Work", "").unwrap();
+        let home = fixture.folder("Home", "").unwrap();
+        let plan = fixture.note("Plan", "", &work).unwrap();
+        let draft = fixture.note("Draft plan", "", &work).unwrap();
+        fixture.note("Groceries", "", &home).unwrap();
+        fixture
+            .execute(&format!(
+                "UPDATE notes SET is_todo = 1, todo_due = 2 WHERE id = '{plan}';
+                 UPDATE notes SET is_todo = 1, todo_completed = 5 WHERE id = '{draft}';"
+            ))
+            .unwrap();
+
+        let titles = |query: &str| -> Vec<String> {
+            let query = Query::parse(query).unwrap();
+            let notes = Note::find(fixture.path(), &query, Include::default()).unwrap();
+            notes.into_iter().map(|note| note.title).collect()
+        };
+        assert_eq!(titles("anything"), vec!["Plan", "Draft plan", "Groceries"]);
+        assert_eq!(titles("nb:work -draft"), vec!["Plan"]);
+        assert_eq!(titles("is:done"), vec!["Draft plan"]);
+        assert_eq!(titles("-nb:WORK -is:todo"), vec!["Groceries"]);
+        assert_eq!(titles("updated:<2000-01-01"), Vec::<String>::new());
+        assert_eq!(titles("is:overdue"), vec!["Plan"]);
+    }
+
+    #[test]
+    fn test_set_completed() {
+        let mut fixture = Fixture::temporary().unwrap();
+        let id = fixture.note("Todo", "", "f").unwrap();
+        fixture
+            .execute(&format!(
+                "UPDATE notes SET is_todo = 1, todo_due = 1000 WHERE id = '{id}'"
+            ))
+            .unwrap();
+        let db_path = fixture.path();
+
+        let todo = Note::get_by_id(db_path, &id).unwrap();
+        assert!(todo.is_open_todo());
+        assert!(todo.is_overdue(from_millis(1001)));
+        assert!(!todo.is_overdue(from_millis(999)));
+
+        let done = todo.set_completed(db_path, true).unwrap();
+        assert!(!done.is_open_todo() && !done.is_overdue(from_millis(1001)));
+        let reloaded = Note::get_by_id(db_path, &id).unwrap();
+        assert_eq!(reloaded.todo_completed, done.todo_completed);
+        assert!(reloaded.updated_time > todo.updated_time);
+
+        let reopened = reloaded.set_completed(db_path, false).unwrap();
+        assert!(reopened.is_open_todo());
+        assert!(Note::get_by_id(db_path, &id).unwrap().is_open_todo());
+    }
+
+    #[test]
+    fn test_hidden_notes_are_excluded_by_default() {
+        let mut fixture = Fixture::temporary().unwrap();
+        for title in ["Kept", "Trashed", "Conflict", ""] {
+            fixture.note(title, "", "f").unwrap();
+        }
+        fixture
+            .execute(
+                "UPDATE notes SET deleted_time = 5 WHERE title = 'Trashed';
+                 UPDATE notes SET is_conflict = 1 WHERE title = 'Conflict';
+                 UPDATE notes SET encryption_applied = 1 WHERE title = '';",
+            )
+            .unwrap();
+
+        let titles = |include: Include| -> Vec<String> {
+            let notes = Note::load_all(fixture.path(), include).unwrap();
+            notes
+                .iter()
+                .map(|note| note.display_title().to_string())
+                .collect()
+        };
+        assert_eq!(titles(Include::default()), vec!["Kept"]);
+        let everything = Include {
+            trashed: true,
+            conflicts: true,
+            encrypted: true,
+        };
+        assert_eq!(
+            titles(everything),
+            vec!["Kept", "Trashed", "Conflict", "🔒 Encrypted note"]
+        );
+        let encrypted = Include {
+            encrypted: true,
+            ..Include::default()
+        };
+        assert_eq!(titles(encrypted), vec!["Kept", "🔒 Encrypted note"]);
     }
 }
-    */
@@ -0,0 +1,114 @@
+use crate::note::Note;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenAction {
+    /// Open the note in the Joplin desktop app through its URL scheme
+    Joplin,
+    /// Write the note to a temporary file and open it in an editor
+    Editor,
+}
+
+/// Shell commands used to open notes, one per action.
+///
+/// The target (a URL or a file path) is appended to the command as a single
+/// quoted argument, so commands may carry their own flags, e.g. `code --wait`.
+#[derive(Debug, Clone)]
+pub struct Opener {
+    pub joplin_command: String,
+    pub editor_command: String,
+}
+
+impl Opener {
+    pub fn new(joplin_command: Option<String>, editor_command: Option<String>) -> Self {
+        let editor_command = editor_command
+            .or_else(|| std::env::var("VISUAL").ok())
+            .or_else(|| std::env::var("EDITOR").ok())
+            .filter(|cmd| !cmd.trim().is_empty())
+            .unwrap_or_else(|| "xdg-open".to_string());
+        Self {
+            joplin_command: joplin_command.unwrap_or_else(|| "xdg-open".to_string()),
+            editor_command,
+        }
+    }
+
+    /// Start the command for the action, without waiting for it to exit
+    pub fn open(&self, action: OpenAction, note: &Note) -> io::Result<()> {
+        match action {
+            OpenAction::Joplin => spawn_shell(&self.joplin_command, &joplin_url(&note.id)),
+            OpenAction::Editor => {
+                let path = export_to_temp(note)?;
+                spawn_shell(&self.editor_command, &path.to_string_lossy())
+            }
+        }
+    }
+}
+
+/// Joplin desktop URL that opens the note with the given id
+pub fn joplin_url(note_id: &str) -> String {
+    format!("joplin://x-callback-url/openNote?id={note_id}")
+}
+
+/// Write the note as markdown to the temp directory, returning its path
+fn export_to_temp(note: &Note) -> io::Result<PathBuf> {
+    let path = std::env::temp_dir().join(format!("{}.md", note.id));
+    std::fs::write(&path, format!("# {}\n\n{}", note.title, note.body))?;
+    Ok(path)
+}
+
+/// Run `command "target"` through `sh` without waiting for it to exit.
+///
+/// The child is reaped from a thread once it exits, so finished editors don't
+/// linger as zombies while the picker stays open.
+fn spawn_shell(command: &str, target: &str) -> io::Result<()> {
+    let mut child = shell_command(command, target).spawn()?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// `sh` running `command "target"`.
+///
+/// Both are passed as arguments of a fixed script rather than pasted into it:
+/// the command is evaluated as shell words, so it can carry its own flags, and
+/// the target is only ever expanded as one quoted argument.
+fn shell_command(command: &str, target: &str) -> Command {
+    let mut sh = Command::new("sh");
+    sh.arg("-c")
+        .arg(r#"eval "$0 \"\$1\"""#)
+        .arg(command)
+        .arg(target);
+    sh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_joplin_url() {
+        assert_eq!(
+            joplin_url("0123456789abcdef0123456789abcdef"),
+            "joplin://x-callback-url/openNote?id=0123456789abcdef0123456789abcdef"
+        );
+    }
+
+    #[test]
+    fn test_explicit_commands_win() {
+        let opener = Opener::new(Some("open".to_string()), Some("code --wait".to_string()));
+        assert_eq!(opener.joplin_command, "open");
+        assert_eq!(opener.editor_command, "code --wait");
+    }
+
+    #[test]
+    fn test_target_is_one_argument() {
+        let output = shell_command("printf '<%s>'", "a b; echo $HOME \"c\"")
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "<a b; echo $HOME \"c\">"
+        );
+    }
+}
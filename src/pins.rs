@@ -0,0 +1,98 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Notes pinned to the top of the picker, kept outside Joplin's database.
+///
+/// Stored as one note id per line in the order they were pinned, rewritten
+/// whenever a note is pinned or unpinned.
+#[derive(Debug, Default)]
+pub struct Pins {
+    path: Option<PathBuf>,
+    ids: Vec<String>,
+}
+
+impl Pins {
+    /// `pins.txt` in the platform's local data directory, next to the history
+    pub fn default_path() -> Option<PathBuf> {
+        Some(
+            dirs::data_local_dir()?
+                .join("chalsedony_selector")
+                .join("pins.txt"),
+        )
+    }
+
+    /// Read the pins file, a missing file is no pins
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let ids = contents
+            .lines()
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            ids,
+        })
+    }
+
+    /// Pinned note ids, oldest pin first
+    pub fn ids(&self) -> &[String] {
+        &self.ids
+    }
+
+    pub fn contains(&self, note_id: &str) -> bool {
+        self.ids.iter().any(|id| id == note_id)
+    }
+
+    /// Pin the note, or unpin it if it was pinned. True when it is now pinned.
+    pub fn toggle(&mut self, note_id: &str) -> io::Result<bool> {
+        let pinned = if self.contains(note_id) {
+            self.ids.retain(|id| id != note_id);
+            false
+        } else {
+            self.ids.push(note_id.to_string());
+            true
+        };
+
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let contents: String = self.ids.iter().map(|id| format!("{id}\n")).collect();
+            fs::write(path, contents)?;
+        }
+        Ok(pinned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toggle_round_trips_through_file() {
+        let path = std::env::temp_dir()
+            .join(format!("chalsedony_pins_{}", crate::note::new_id()))
+            .join("pins.txt");
+        let mut pins = Pins::load(&path).unwrap();
+        assert!(pins.ids().is_empty());
+
+        assert!(pins.toggle("a").unwrap());
+        assert!(pins.toggle("b").unwrap());
+        assert!(!pins.toggle("a").unwrap());
+        assert!(pins.toggle("c").unwrap());
+        let reloaded = Pins::load(&path).unwrap();
+        assert_eq!(reloaded.ids(), ["b", "c"]);
+        assert!(reloaded.contains("c"));
+        assert!(!reloaded.contains("a"));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,592 @@
+use crate::bm25::{FieldWeights, NoteIndex};
+use crate::dates::{day_end, day_start};
+use crate::note::Note;
+use crate::tokenizer::is_cjk;
+use chrono::NaiveDate;
+use ordered_float::OrderedFloat;
+use rusqlite::types::Value;
+use std::cmp::Reverse;
+use std::fmt;
+
+/// How an `updated:` date is compared with the day a note was last modified
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateComparison {
+    Before,
+    OnOrBefore,
+    On,
+    OnOrAfter,
+    After,
+}
+
+/// A field prefixed term of a query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    /// Title contains the text, only used for negated bare words
+    Title(String),
+    /// `b:`, full-text search
+    Body(String),
+    /// `nb:`, notebook id or title
+    Notebook(String),
+    /// `tag:`, tag title
+    Tag(String),
+    /// `is:todo`, to-do not completed yet
+    Todo,
+    /// `is:done`, completed to-do
+    Done,
+    /// `is:overdue`, to-do not completed by its due time
+    Overdue,
+    /// `updated:>2024-01-01`
+    Updated(DateComparison, NaiveDate),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    /// Prefixed with `-`, matching notes are excluded
+    pub negated: bool,
+    pub filter: Filter,
+}
+
+/// A `b:` term searching bodies for `text`, quoted as a phrase when it has
+/// several words. Quotes inside the text can't be expressed and are dropped.
+pub fn body_term(text: &str) -> String {
+    let text: String = text.chars().filter(|&c| c != '"').collect();
+    let text = text.trim();
+    if text.contains(char::is_whitespace) {
+        format!("b:\"{text}\"")
+    } else {
+        format!("b:{text}")
+    }
+}
+
+/// A parsed search query.
+///
+/// Bare words and `"quoted phrases"` match titles fuzzily, the other terms
+/// filter by field:
+///
+/// ```text
+/// meeting "next steps" b:budget nb:Work tag:urgent is:todo updated:>=2024-01-01 -draft
+/// ```
+///
+/// Prefixes are only recognised outside quotes, so `"b:c"` is a phrase.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    /// Bare words and phrases, ranked fuzzily against titles and bodies
+    pub words: Vec<String>,
+    pub conditions: Vec<Condition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// `is:` followed by something other than `todo`, `done` or `overdue`
+    UnknownIs(String),
+    /// `updated:` not followed by an optional comparison and a `YYYY-MM-DD` date
+    InvalidDate(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnknownIs(value) => {
+                write!(
+                    f,
+                    "unknown \"is:{value}\", expected is:todo, is:done or is:overdue"
+                )
+            }
+            ParseError::InvalidDate(value) => {
+                write!(
+                    f,
+                    "invalid date \"{value}\", expected e.g. updated:>2024-01-01"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Whitespace separated chunk of the input with its quotes removed
+struct Token {
+    text: String,
+    /// Byte offset in `text` where the first quoted part starts
+    quoted_from: Option<usize>,
+}
+
+fn tokens(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current: Option<Token> = None;
+    let mut in_quotes = false;
+
+    for c in input.chars() {
+        if c.is_whitespace() && !in_quotes {
+            tokens.extend(current.take());
+            continue;
+        }
+        let token = current.get_or_insert_with(|| Token {
+            text: String::new(),
+            quoted_from: None,
+        });
+        if c == '"' {
+            // An unclosed quote runs to the end, as the query is usually still being typed
+            in_quotes = !in_quotes;
+            token.quoted_from.get_or_insert(token.text.len());
+        } else {
+            token.text.push(c);
+        }
+    }
+    tokens.extend(current);
+    tokens
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        let mut query = Query::default();
+
+        for token in tokens(input) {
+            let unquoted = |end: usize| token.quoted_from.is_none_or(|quoted| end <= quoted);
+            let mut text = token.text.as_str();
+            let mut start = 0;
+            let negated = text.len() > 1 && text.starts_with('-') && unquoted(1);
+            if negated {
+                text = &text[1..];
+                start = 1;
+            }
+
+            let prefixed = text
+                .split_once(':')
+                .map(|(prefix, value)| (prefix.to_lowercase(), value))
+                .filter(|(prefix, _)| {
+                    unquoted(start + prefix.len() + 1)
+                        && matches!(prefix.as_str(), "b" | "nb" | "tag" | "is" | "updated")
+                });
+            let filter = match prefixed
+                .as_ref()
+                .map(|(prefix, value)| (prefix.as_str(), *value))
+            {
+                // Terms still being typed are ignored rather than errors
+                Some((_, "")) => continue,
+                Some(("b", value)) => Filter::Body(value.to_string()),
+                Some(("nb", value)) => Filter::Notebook(value.to_string()),
+                Some(("tag", value)) => Filter::Tag(value.to_string()),
+                Some(("is", value)) => match value.to_lowercase().as_str() {
+                    "todo" => Filter::Todo,
+                    "done" => Filter::Done,
+                    "overdue" => Filter::Overdue,
+                    _ => return Err(ParseError::UnknownIs(value.to_string())),
+                },
+                Some((_, value)) => parse_updated(value)?,
+                None if text.is_empty() => continue,
+                None if !negated => {
+                    query.words.push(text.to_string());
+                    continue;
+                }
+                None => Filter::Title(text.to_string()),
+            };
+            query.conditions.push(Condition { negated, filter });
+        }
+
+        Ok(query)
+    }
+
+    /// The bare words, e.g. the title of a note created from the query
+    pub fn text(&self) -> String {
+        self.words.join(" ")
+    }
+
+    /// Whether [`Query::rank`] scores notes, rather than only ordering them by the tie break
+    pub fn has_ranking_terms(&self) -> bool {
+        !self.words.is_empty()
+            || self
+                .conditions
+                .iter()
+                .any(|condition| !condition.negated && matches!(condition.filter, Filter::Body(_)))
+    }
+
+    /// `WHERE` clause selecting the notes matching every condition, with its parameters.
+    ///
+    /// `b:` terms are looked up in the `notes_fts5_porter` full text index when
+    /// `full_text` is set. Without the index, bodies are searched for the term
+    /// as a substring instead. So is Chinese and Japanese text, which the index
+    /// keeps as one token per run of characters. A body has all the character
+    /// bigrams of such a term, adjacent and in order, exactly when it contains
+    /// the term, so the substring is the bigram match done in SQL. How well the
+    /// matches score is left to the bigrams of [`Query::rank`].
+    pub fn sql(&self, full_text: bool) -> (String, Vec<Value>) {
+        let mut clauses = Vec::new();
+        let mut params = Vec::new();
+
+        for condition in &self.conditions {
+            let clause = match &condition.filter {
+                Filter::Title(text) => {
+                    params.push(Value::Text(text.clone()));
+                    "instr(lower(notes.title), lower(?)) > 0".to_string()
+                }
+                Filter::Body(text) if !full_text || text.chars().any(is_cjk) => {
+                    let text = text.strip_suffix('*').unwrap_or(text);
+                    params.push(Value::Text(text.to_string()));
+                    "instr(lower(notes.body), lower(?)) > 0".to_string()
+                }
+                Filter::Body(text) => {
+                    params.push(Value::Text(fts_phrase(text)));
+                    "notes.id IN (SELECT id FROM notes_fts5_porter WHERE notes_fts5_porter MATCH ?)"
+                        .to_string()
+                }
+                Filter::Notebook(notebook) => {
+                    params.push(Value::Text(notebook.clone()));
+                    params.push(Value::Text(notebook.clone()));
+                    "notes.parent_id IN (SELECT id FROM folders WHERE id = ? OR lower(title) = lower(?))"
+                        .to_string()
+                }
+                Filter::Tag(tag) => {
+                    params.push(Value::Text(tag.clone()));
+                    "notes.id IN (SELECT note_tags.note_id FROM note_tags
+                                  JOIN tags ON tags.id = note_tags.tag_id
+                                  WHERE lower(tags.title) = lower(?))"
+                        .to_string()
+                }
+                Filter::Todo => "(notes.is_todo = 1 AND notes.todo_completed = 0)".to_string(),
+                Filter::Done => "(notes.is_todo = 1 AND notes.todo_completed != 0)".to_string(),
+                Filter::Overdue => {
+                    params.push(Value::Integer(chrono::Utc::now().timestamp_millis()));
+                    "(notes.is_todo = 1 AND notes.todo_completed = 0
+                      AND notes.todo_due != 0 AND notes.todo_due < ?)"
+                        .to_string()
+                }
+                Filter::Updated(comparison, date) => {
+                    let start = day_start(*date).timestamp_millis();
+                    let end = day_end(*date).timestamp_millis();
+                    let column = "notes.user_updated_time";
+                    match comparison {
+                        DateComparison::Before => {
+                            params.push(Value::Integer(start));
+                            format!("{column} < ?")
+                        }
+                        DateComparison::OnOrBefore => {
+                            params.push(Value::Integer(end));
+                            format!("{column} < ?")
+                        }
+                        DateComparison::On => {
+                            params.push(Value::Integer(start));
+                            params.push(Value::Integer(end));
+                            format!("({column} >= ? AND {column} < ?)")
+                        }
+                        DateComparison::OnOrAfter => {
+                            params.push(Value::Integer(start));
+                            format!("{column} >= ?")
+                        }
+                        DateComparison::After => {
+                            params.push(Value::Integer(end));
+                            format!("{column} >= ?")
+                        }
+                    }
+                }
+            };
+            clauses.push(if condition.negated {
+                format!("NOT {clause}")
+            } else {
+                clause
+            });
+        }
+
+        if clauses.is_empty() {
+            ("1".to_string(), params)
+        } else {
+            (clauses.join(" AND "), params)
+        }
+    }
+
+    /// Drop notes the bare words don't match and order the rest best first.
+    ///
+    /// Notes are scored by the bare words and the `b:` terms over titles and
+    /// bodies. Without either they are only ordered by `tie_break`, highest first.
+    pub fn rank(
+        &self,
+        notes: &mut Vec<Note>,
+        index: &NoteIndex,
+        weights: FieldWeights,
+        tie_break: impl Fn(&Note) -> OrderedFloat<f64>,
+    ) {
+        let mut scores = index.scores(&self.text(), weights);
+        if !self.words.is_empty() {
+            notes.retain(|note| scores.contains_key(&note.id));
+        }
+        for condition in &self.conditions {
+            if let (false, Filter::Body(text)) = (condition.negated, &condition.filter) {
+                // Body terms are matched as written, typos are corrected to
+                // title words
+                for (id, score) in index.term_scores(text, weights) {
+                    *scores.entry(id).or_default() += score;
+                }
+            }
+        }
+
+        let score = |note: &Note| OrderedFloat(scores.get(&note.id).copied().unwrap_or(0.0));
+        notes.sort_by_key(|note| Reverse((score(note), tie_break(note))));
+    }
+}
+
+fn parse_updated(value: &str) -> Result<Filter, ParseError> {
+    let (comparison, date) = if let Some(date) = value.strip_prefix(">=") {
+        (DateComparison::OnOrAfter, date)
+    } else if let Some(date) = value.strip_prefix("<=") {
+        (DateComparison::OnOrBefore, date)
+    } else if let Some(date) = value.strip_prefix('>') {
+        (DateComparison::After, date)
+    } else if let Some(date) = value.strip_prefix('<') {
+        (DateComparison::Before, date)
+    } else {
+        (DateComparison::On, value.strip_prefix('=').unwrap_or(value))
+    };
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| ParseError::InvalidDate(value.to_string()))?;
+    Ok(Filter::Updated(comparison, date))
+}
+
+/// FTS5 phrase matching the text literally, a trailing `*` still matches prefixes
+fn fts_phrase(text: &str) -> String {
+    let (text, star) = match text.strip_suffix('*') {
+        Some(text) => (text, "*"),
+        None => (text, ""),
+    };
+    format!("\"{}\"{star}", text.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn condition(negated: bool, filter: Filter) -> Condition {
+        Condition { negated, filter }
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_bare_words_and_phrases() {
+        let query = Query::parse(r#"  meeting "next steps"  notes "#).unwrap();
+        assert_eq!(query.words, vec!["meeting", "next steps", "notes"]);
+        assert!(query.conditions.is_empty());
+        assert_eq!(query.text(), "meeting next steps notes");
+    }
+
+    #[test]
+    fn test_prefixes() {
+        let query =
+            Query::parse(r#"b:"annual budget" nb:Work tag:urgent is:todo IS:Done is:overdue"#)
+                .unwrap();
+        assert!(query.words.is_empty());
+        assert_eq!(
+            query.conditions,
+            vec![
+                condition(false, Filter::Body("annual budget".to_string())),
+                condition(false, Filter::Notebook("Work".to_string())),
+                condition(false, Filter::Tag("urgent".to_string())),
+                condition(false, Filter::Todo),
+                condition(false, Filter::Done),
+                condition(false, Filter::Overdue),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_negation() {
+        let query = Query::parse(r#"-draft -b:secret -"old plan" - x-ray"#).unwrap();
+        assert_eq!(query.words, vec!["-", "x-ray"]);
+        assert_eq!(
+            query.conditions,
+            vec![
+                condition(true, Filter::Title("draft".to_string())),
+                condition(true, Filter::Body("secret".to_string())),
+                condition(true, Filter::Title("old plan".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_body_term_round_trips() {
+        for (text, expected) in [
+            ("budget", "budget"),
+            (" annual budget ", "annual budget"),
+            ("say \"hi\" there", "say hi there"),
+        ] {
+            let query = Query::parse(&body_term(text)).unwrap();
+            assert_eq!(
+                query.conditions,
+                vec![condition(false, Filter::Body(expected.to_string()))]
+            );
+        }
+    }
+
+    #[test]
+    fn test_quoted_and_unknown_prefixes_are_words() {
+        let query = Query::parse(r#""b:c" http://example.com b: "unclosed phrase"#).unwrap();
+        assert_eq!(
+            query.words,
+            vec!["b:c", "http://example.com", "unclosed phrase"]
+        );
+        assert!(query.conditions.is_empty());
+    }
+
+    #[test]
+    fn test_updated() {
+        let query =
+            Query::parse("updated:>2024-01-01 updated:<=2024-02-29 updated:2024-03-01").unwrap();
+        assert_eq!(
+            query.conditions,
+            vec![
+                condition(
+                    false,
+                    Filter::Updated(DateComparison::After, date("2024-01-01"))
+                ),
+                condition(
+                    false,
+                    Filter::Updated(DateComparison::OnOrBefore, date("2024-02-29"))
+                ),
+                condition(
+                    false,
+                    Filter::Updated(DateComparison::On, date("2024-03-01"))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Query::parse("is:maybe"),
+            Err(ParseError::UnknownIs("maybe".to_string()))
+        );
+        assert_eq!(
+            Query::parse("updated:>yesterday"),
+            Err(ParseError::InvalidDate(">yesterday".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_sql() {
+        assert_eq!(
+            Query::parse("just words").unwrap().sql(true),
+            ("1".to_string(), vec![])
+        );
+
+        let (sql, params) = Query::parse(r#"-b:"say hi" is:done"#).unwrap().sql(true);
+        assert!(sql.starts_with("NOT notes.id IN (SELECT id FROM notes_fts5_porter"));
+        assert!(sql.ends_with(" AND (notes.is_todo = 1 AND notes.todo_completed != 0)"));
+        assert_eq!(params, vec![Value::Text(r#""say hi""#.to_string())]);
+
+        let (sql, params) = Query::parse("updated:2024-01-01").unwrap().sql(true);
+        assert_eq!(
+            sql,
+            "(notes.user_updated_time >= ? AND notes.user_updated_time < ?)"
+        );
+        let [Value::Integer(start), Value::Integer(end)] = params[..] else {
+            panic!("expected two integers, got {params:?}");
+        };
+        // A day, or 23 or 25 hours around daylight saving changes
+        assert!((23 * 3_600_000..=25 * 3_600_000).contains(&(end - start)));
+
+        let substring = "instr(lower(notes.body), lower(?)) > 0";
+        let (sql, params) = Query::parse("b:日本語").unwrap().sql(true);
+        assert_eq!(sql, substring);
+        assert_eq!(params, vec![Value::Text("日本語".to_string())]);
+        let (sql, params) = Query::parse("b:budg*").unwrap().sql(false);
+        assert_eq!(sql, substring);
+        assert_eq!(params, vec![Value::Text("budg".to_string())]);
+    }
+
+    #[test]
+    fn test_fts_phrase() {
+        assert_eq!(fts_phrase("foo-bar"), r#""foo-bar""#);
+        assert_eq!(fts_phrase("budg*"), r#""budg"*"#);
+        assert_eq!(fts_phrase(r#"a"b"#), r#""a""b""#);
+    }
+
+    #[test]
+    fn test_find_and_rank_cjk() {
+        use crate::fixture::Fixture;
+        use crate::note::Include;
+        use crate::tokenizer::TokenizerConfig;
+
+        let mut fixture = Fixture::temporary().unwrap();
+        let folder = fixture.folder("メモ", "").unwrap();
+        let tokyo = fixture.note("東京", "東京都の天気予報", &folder).unwrap();
+        let kyoto = fixture.note("京都", "京都の紅葉と日本語", &folder).unwrap();
+        fixture.note("Notes", "plain english", &folder).unwrap();
+
+        let find = |query: &str| {
+            let query = Query::parse(query).unwrap();
+            let mut notes = Note::find(fixture.path(), &query, Include::default()).unwrap();
+            let index = NoteIndex::new(&notes, TokenizerConfig::default().build());
+            query.rank(&mut notes, &index, FieldWeights::default(), |_| {
+                OrderedFloat(0.0)
+            });
+            notes.into_iter().map(|n| n.id).collect::<Vec<_>>()
+        };
+        assert_eq!(find("b:紅葉"), vec![kyoto.clone()]);
+        assert_eq!(find("b:天気"), vec![tokyo.clone()]);
+        assert_eq!(find("京都"), vec![kyoto, tokyo]);
+    }
+
+    #[test]
+    fn test_cjk_body_fallback_ranks_by_bigrams() {
+        use crate::fixture::Fixture;
+        use crate::note::Include;
+        use crate::tokenizer::TokenizerConfig;
+
+        let mut fixture = Fixture::temporary().unwrap();
+        let folder = fixture.folder("メモ", "").unwrap();
+        let once = fixture
+            .note("予報", "明日の天気は晴れ、週末は雨の予報です", &folder)
+            .unwrap();
+        let often = fixture
+            .note("雑記", "天気、天気、天気の日記", &folder)
+            .unwrap();
+        fixture.note("日記", "天と気", &folder).unwrap();
+        // As on a database without the full text index
+        fixture
+            .execute("DROP TRIGGER notes_ai; DROP TRIGGER notes_ad; DROP TRIGGER notes_au; DROP TABLE notes_fts5_porter;")
+            .unwrap();
+
+        let find = |query: &str| {
+            let query = Query::parse(query).unwrap();
+            let mut notes = Note::find(fixture.path(), &query, Include::default()).unwrap();
+            let index = NoteIndex::new(&notes, TokenizerConfig::default().build());
+            query.rank(&mut notes, &index, FieldWeights::default(), |_| {
+                OrderedFloat(0.0)
+            });
+            notes.into_iter().map(|n| n.id).collect::<Vec<_>>()
+        };
+        assert_eq!(find("b:天気"), vec![often.clone(), once.clone()]);
+        assert_eq!(find("b:天気は晴れ"), vec![once]);
+        assert!(find("b:天晴").is_empty());
+    }
+
+    #[test]
+    fn test_body_terms_are_not_corrected() {
+        use crate::tokenizer::{TokenizerConfig, TokenizerKind};
+
+        let note = |id: &str, title: &str, body: &str| Note {
+            body: body.to_string(),
+            ..Note::for_test(id, title)
+        };
+        let mut notes = vec![
+            note("meeting", "Meeting notes", "Agenda"),
+            note(
+                "typo",
+                "Minutes",
+                "The meetng ran late into the evening again",
+            ),
+        ];
+        let words = TokenizerConfig {
+            kind: TokenizerKind::Words,
+            ..TokenizerConfig::default()
+        };
+        let index = NoteIndex::new(&notes, words.build());
+        let query = Query::parse("b:meetng").unwrap();
+        query.rank(&mut notes, &index, FieldWeights::default(), |_| {
+            OrderedFloat(0.0)
+        });
+        assert_eq!(notes[0].id, "typo");
+    }
+}
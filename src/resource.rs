@@ -0,0 +1,91 @@
+use crate::links::linked_ids;
+use crate::note::{open_read_only, table_exists};
+use rusqlite::{OptionalExtension, Result as SqlResult};
+use std::path::{Path, PathBuf};
+
+/// An attachment stored in Joplin's `resources` table.
+///
+/// The file itself lives in the `resources/` directory next to the database,
+/// named `<id>.<file_extension>`.
+#[derive(Debug, Clone)]
+pub struct Resource {
+    pub id: String,
+    pub title: String,
+    pub mime: String,
+    pub file_extension: String,
+}
+
+impl Resource {
+    pub fn get_by_id(db_path: &str, resource_id: &str) -> SqlResult<Resource> {
+        let conn = open_read_only(db_path)?;
+
+        let mut stmt =
+            conn.prepare("SELECT id, title, mime, file_extension FROM resources WHERE id = ?")?;
+        stmt.query_row([resource_id], Self::from_row)
+    }
+
+    /// Resources attached to a note.
+    ///
+    /// Includes resources referenced by `:/id` in the body and, when the
+    /// database has it, those associated with the note in `note_resources`.
+    pub fn linked_from(db_path: &str, note_id: &str) -> SqlResult<Vec<Resource>> {
+        let conn = open_read_only(db_path)?;
+
+        let body: String =
+            conn.query_row("SELECT body FROM notes WHERE id = ?", [note_id], |row| {
+                row.get(0)
+            })?;
+        let mut ids = linked_ids(&body);
+        if table_exists(&conn, "note_resources")? {
+            let mut stmt = conn.prepare(
+                "SELECT resource_id FROM note_resources WHERE note_id = ? AND is_associated = 1",
+            )?;
+            for id in stmt.query_map([note_id], |row| row.get::<_, String>(0))? {
+                let id = id?;
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+
+        let mut stmt =
+            conn.prepare("SELECT id, title, mime, file_extension FROM resources WHERE id = ?")?;
+        let mut resources = Vec::new();
+        for id in ids {
+            resources.extend(stmt.query_row([&id], Self::from_row).optional()?);
+        }
+
+        Ok(resources)
+    }
+
+    fn from_row(row: &rusqlite::Row) -> SqlResult<Resource> {
+        Ok(Resource {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            mime: row.get(2)?,
+            file_extension: row.get(3)?,
+        })
+    }
+
+    /// Directory Joplin keeps resource files in, alongside the database
+    pub fn dir(db_path: &str) -> PathBuf {
+        Path::new(db_path)
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("resources")
+    }
+
+    /// Location of the resource file on disk
+    pub fn path(&self, db_path: &str) -> PathBuf {
+        let file_name = if self.file_extension.is_empty() {
+            self.id.clone()
+        } else {
+            format!("{}.{}", self.id, self.file_extension)
+        };
+        Self::dir(db_path).join(file_name)
+    }
+
+    pub fn is_image(&self) -> bool {
+        self.mime.starts_with("image/")
+    }
+}
@@ -0,0 +1,185 @@
+use crate::folder::Folder;
+use crate::links::linked_ids;
+use crate::note::{open_read_only, Note};
+use clap::ValueEnum;
+use rusqlite::Result as SqlResult;
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::HashMap;
+
+/// Order of the note list, applied after filtering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+pub enum SortMode {
+    /// Order given by the filters, best match first
+    #[default]
+    Relevance,
+    /// Most recently modified first
+    Updated,
+    /// Most recently created first
+    Created,
+    /// Title A–Z
+    Title,
+    /// Most linked to from other notes first
+    Linked,
+    /// Notebook title A–Z, then note title
+    Notebook,
+}
+
+impl SortMode {
+    pub const ALL: [SortMode; 6] = [
+        SortMode::Relevance,
+        SortMode::Updated,
+        SortMode::Created,
+        SortMode::Title,
+        SortMode::Linked,
+        SortMode::Notebook,
+    ];
+
+    /// Whether sorting needs a [`SortContext`] loaded from the database
+    pub fn needs_context(&self) -> bool {
+        matches!(self, SortMode::Linked | SortMode::Notebook)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortMode::Relevance => "Relevance",
+            SortMode::Updated => "Last updated",
+            SortMode::Created => "Created",
+            SortMode::Title => "Title A–Z",
+            SortMode::Linked => "Most linked",
+            SortMode::Notebook => "Notebook",
+        }
+    }
+}
+
+/// Database-wide data some sort modes need, loaded once and reused across sorts
+#[derive(Debug, Default)]
+pub struct SortContext {
+    /// Notebook title for each notebook id
+    pub folder_titles: HashMap<String, String>,
+    /// Number of other notes linking to each note id
+    pub link_counts: HashMap<String, usize>,
+}
+
+impl SortContext {
+    pub fn load(db_path: &str) -> SqlResult<Self> {
+        let folder_titles = Folder::load_all(db_path)?
+            .into_iter()
+            .map(|folder| (folder.id, folder.title))
+            .collect();
+
+        let conn = open_read_only(db_path)?;
+        let mut stmt = conn.prepare("SELECT id, body FROM notes")?;
+        let mut link_counts: HashMap<String, usize> = HashMap::new();
+        for row in stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })? {
+            let (id, body) = row?;
+            for target in linked_ids(&body) {
+                if target != id {
+                    *link_counts.entry(target).or_default() += 1;
+                }
+            }
+        }
+
+        Ok(Self {
+            folder_titles,
+            link_counts,
+        })
+    }
+}
+
+/// Sort notes in place, ties keep their current (relevance) order
+pub fn sort_notes(notes: &mut [Note], mode: SortMode, context: &SortContext) {
+    let by_title = |a: &Note, b: &Note| a.title.to_lowercase().cmp(&b.title.to_lowercase());
+    match mode {
+        SortMode::Relevance => {}
+        SortMode::Updated => notes.sort_by_key(|note| Reverse(note.user_updated_time)),
+        SortMode::Created => notes.sort_by_key(|note| Reverse(note.user_created_time)),
+        SortMode::Title => notes.sort_by(by_title),
+        SortMode::Linked => {
+            let count = |note: &Note| context.link_counts.get(&note.id).copied().unwrap_or(0);
+            notes.sort_by_key(|note| Reverse(count(note)));
+        }
+        SortMode::Notebook => {
+            let notebook = |note: &Note| {
+                context
+                    .folder_titles
+                    .get(&note.parent_id)
+                    .map(|title| title.to_lowercase())
+                    .unwrap_or_default()
+            };
+            notes.sort_by(|a, b| match notebook(a).cmp(&notebook(b)) {
+                Ordering::Equal => by_title(a, b),
+                ordering => ordering,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dates::from_millis;
+
+    fn note(id: &str, title: &str, parent_id: &str, created: i64, updated: i64) -> Note {
+        Note {
+            parent_id: parent_id.to_string(),
+            updated_time: from_millis(updated),
+            user_created_time: from_millis(created),
+            user_updated_time: from_millis(updated),
+            ..Note::for_test(id, title)
+        }
+    }
+
+    fn ids(notes: &[Note]) -> Vec<&str> {
+        notes.iter().map(|n| n.id.as_str()).collect()
+    }
+
+    fn notes() -> Vec<Note> {
+        vec![
+            note("a", "banana", "f2", 1, 30),
+            note("b", "Apple", "f1", 3, 10),
+            note("c", "cherry", "f1", 2, 20),
+        ]
+    }
+
+    #[test]
+    fn test_relevance_keeps_order() {
+        let mut notes = notes();
+        sort_notes(&mut notes, SortMode::Relevance, &SortContext::default());
+        assert_eq!(ids(&notes), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_times_newest_first() {
+        let mut notes = notes();
+        sort_notes(&mut notes, SortMode::Updated, &SortContext::default());
+        assert_eq!(ids(&notes), vec!["a", "c", "b"]);
+        sort_notes(&mut notes, SortMode::Created, &SortContext::default());
+        assert_eq!(ids(&notes), vec!["b", "c", "a"]);
+    }
+
+    #[test]
+    fn test_title_ignores_case() {
+        let mut notes = notes();
+        sort_notes(&mut notes, SortMode::Title, &SortContext::default());
+        assert_eq!(ids(&notes), vec!["b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_linked_and_notebook() {
+        let context = SortContext {
+            folder_titles: HashMap::from([
+                ("f1".to_string(), "Work".to_string()),
+                ("f2".to_string(), "Home".to_string()),
+            ]),
+            link_counts: HashMap::from([("c".to_string(), 5), ("b".to_string(), 1)]),
+        };
+        let mut notes = notes();
+        sort_notes(&mut notes, SortMode::Linked, &context);
+        assert_eq!(ids(&notes), vec!["c", "b", "a"]);
+        sort_notes(&mut notes, SortMode::Notebook, &context);
+        assert_eq!(ids(&notes), vec!["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,553 @@
+//! Splitting note text into the terms notes are ranked by.
+
+use clap::ValueEnum;
+use rust_stemmers::{Algorithm, Stemmer};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Splits text into terms.
+///
+/// Terms are passed to a callback rather than returned, so a tokenizer can
+/// hand out slices of the text it works on without allocating each one.
+pub trait Tokenizer: fmt::Debug + Send + Sync {
+    /// Call `term` with each term of `text` in order, repeats included
+    fn tokenize(&self, text: &str, term: &mut dyn FnMut(&str));
+
+    /// The terms of `text`, owned
+    fn terms(&self, text: &str) -> Vec<String> {
+        let mut terms = Vec::new();
+        self.tokenize(text, &mut |t| terms.push(t.to_string()));
+        terms
+    }
+
+    /// How text is normalised before it is split, so words compared with the
+    /// terms can be normalised alike
+    fn normalization(&self) -> Normalization {
+        Normalization::default()
+    }
+}
+
+impl<T: Tokenizer + ?Sized> Tokenizer for Box<T> {
+    fn tokenize(&self, text: &str, term: &mut dyn FnMut(&str)) {
+        (**self).tokenize(text, term)
+    }
+
+    fn normalization(&self) -> Normalization {
+        (**self).normalization()
+    }
+}
+
+/// How text is normalised before it is split into terms
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Normalization {
+    /// Match letters regardless of accents, so "cafe" finds "Café"
+    pub strip_diacritics: bool,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self {
+            strip_diacritics: true,
+        }
+    }
+}
+
+/// Case folded text in composed form, with compatibility characters such as
+/// ligatures and full width letters replaced by their plain forms
+pub fn normalize(s: &str, normalization: Normalization) -> String {
+    let mut folded = String::with_capacity(s.len());
+    for c in s.nfkd() {
+        if normalization.strip_diacritics && is_diacritic(c) {
+            continue;
+        }
+        match c {
+            'ß' | 'ẞ' => folded.push_str("ss"),
+            'ς' => folded.push('σ'),
+            c => folded.extend(c.to_lowercase()),
+        }
+    }
+    folded.nfc().collect()
+}
+
+/// Combining marks that accent Latin, Greek and Cyrillic letters. Marks that
+/// are part of a letter in other scripts, e.g. Devanagari vowel signs or
+/// the kana voicing marks, are kept.
+fn is_diacritic(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+/// How n-grams are cut from text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Segmentation {
+    /// Character trigrams throughout
+    Trigrams,
+    /// Bigrams within runs of Chinese and Japanese characters, which are
+    /// written without spaces and have many two character words, trigrams
+    /// elsewhere. The same as trigrams for text without them.
+    #[default]
+    Cjk,
+}
+
+/// Whether a character is Han or Kana, i.e. part of a run of Chinese or
+/// Japanese text without spaces between words
+pub fn is_cjk(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3005}'..='\u{3007}' // 々 〆 〇
+            | '\u{3040}'..='\u{30FF}' // Hiragana, Katakana
+            | '\u{31F0}'..='\u{31FF}' // Katakana phonetic extensions
+            | '\u{3400}'..='\u{4DBF}' // CJK extension A
+            | '\u{4E00}'..='\u{9FFF}' // CJK unified ideographs
+            | '\u{F900}'..='\u{FAFF}' // CJK compatibility ideographs
+            | '\u{20000}'..='\u{3134F}' // CJK extensions B to G
+    )
+}
+
+/// Trigrams of user-perceived characters of normalised text, the whole
+/// text when it is shorter
+pub fn ngram_tokenize(s: &str) -> Vec<String> {
+    NGrams::trigrams().terms(s)
+}
+
+/// Words separated by whitespace, exactly as written
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Whitespace;
+
+impl Tokenizer for Whitespace {
+    fn tokenize(&self, text: &str, term: &mut dyn FnMut(&str)) {
+        text.split_whitespace().for_each(term);
+    }
+}
+
+/// Character n-grams of normalised text, which match misspellings and parts
+/// of words
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NGrams {
+    /// Characters in each n-gram
+    pub n: usize,
+    /// Cut n-grams from each word with a space either side rather than from
+    /// the whole text, so n-grams never span two words and those at the
+    /// start and end of a word are told apart from the ones within it
+    pub pad_words: bool,
+    pub segmentation: Segmentation,
+    pub normalization: Normalization,
+}
+
+impl Default for NGrams {
+    fn default() -> Self {
+        Self {
+            n: 3,
+            pad_words: false,
+            segmentation: Segmentation::default(),
+            normalization: Normalization::default(),
+        }
+    }
+}
+
+impl NGrams {
+    /// Trigrams throughout, as [`ngram_tokenize`] cuts them
+    pub fn trigrams() -> Self {
+        Self {
+            segmentation: Segmentation::Trigrams,
+            ..Self::default()
+        }
+    }
+
+    /// N-grams of normalised text, bigrams in runs of Chinese and Japanese
+    /// when segmenting them
+    fn segment(&self, text: &str, term: &mut dyn FnMut(&str)) {
+        if self.segmentation == Segmentation::Trigrams {
+            return ngrams(text, self.n, term);
+        }
+        let n = |cjk: bool| if cjk { self.n.min(2) } else { self.n };
+        let mut start = 0;
+        let mut run_is_cjk = None;
+        for (i, grapheme) in text.grapheme_indices(true) {
+            let cjk = grapheme.chars().next().is_some_and(is_cjk);
+            if let Some(run) = run_is_cjk.filter(|&run| run != cjk) {
+                ngrams(&text[start..i], n(run), term);
+                start = i;
+            }
+            run_is_cjk = Some(cjk);
+        }
+        if let Some(run) = run_is_cjk {
+            ngrams(&text[start..], n(run), term);
+        }
+    }
+}
+
+/// Windows of `n` graphemes, so an emoji sequence or a letter with a mark
+/// that has no composed form is never split, all of them when there are fewer
+fn ngrams(text: &str, n: usize, term: &mut dyn FnMut(&str)) {
+    let n = n.max(1);
+    let bounds: Vec<usize> = text
+        .grapheme_indices(true)
+        .map(|(i, _)| i)
+        .chain([text.len()])
+        .collect();
+    let graphemes = bounds.len() - 1;
+    if graphemes == 0 {
+        return;
+    }
+    if graphemes < n {
+        return term(text);
+    }
+    for start in 0..=graphemes - n {
+        term(&text[bounds[start]..bounds[start + n]]);
+    }
+}
+
+impl Tokenizer for NGrams {
+    fn tokenize(&self, text: &str, term: &mut dyn FnMut(&str)) {
+        let text = normalize(text, self.normalization);
+        if !self.pad_words {
+            return self.segment(&text, term);
+        }
+        for word in text.split_whitespace() {
+            // Words of Chinese or Japanese are whole phrases, padding them
+            // would only add n-grams of spaces and a character
+            if self.segmentation == Segmentation::Cjk && word.chars().any(is_cjk) {
+                self.segment(word, term);
+            } else {
+                self.segment(&format!(" {word} "), term);
+            }
+        }
+    }
+
+    fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+}
+
+/// Words of normalised text, split at word boundaries without punctuation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Words {
+    pub normalization: Normalization,
+}
+
+impl Tokenizer for Words {
+    fn tokenize(&self, text: &str, term: &mut dyn FnMut(&str)) {
+        normalize(text, self.normalization)
+            .unicode_words()
+            .for_each(term);
+    }
+
+    fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+}
+
+/// Words reduced to their stem by the Porter (Snowball English) stemmer, so
+/// "running" and "runs" both match "run"
+pub struct Stemmed {
+    normalization: Normalization,
+    stemmer: Stemmer,
+}
+
+impl Stemmed {
+    pub fn english(normalization: Normalization) -> Self {
+        Self {
+            normalization,
+            stemmer: Stemmer::create(Algorithm::English),
+        }
+    }
+}
+
+impl fmt::Debug for Stemmed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stemmed")
+            .field("normalization", &self.normalization)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Tokenizer for Stemmed {
+    fn tokenize(&self, text: &str, term: &mut dyn FnMut(&str)) {
+        for word in normalize(text, self.normalization).unicode_words() {
+            term(&self.stemmer.stem(word));
+        }
+    }
+
+    fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+}
+
+/// Common English words, they match almost every note and only add noise
+const ENGLISH_STOPWORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "an", "and", "any", "are", "as", "at", "be", "because",
+    "been", "but", "by", "can", "could", "did", "do", "does", "for", "from", "had", "has", "have",
+    "he", "her", "his", "how", "i", "if", "in", "into", "is", "it", "its", "just", "me", "my",
+    "no", "not", "of", "on", "one", "or", "our", "out", "she", "so", "some", "than", "that", "the",
+    "their", "them", "then", "there", "these", "they", "this", "to", "up", "us", "was", "we",
+    "were", "what", "when", "which", "who", "will", "with", "would", "you", "your",
+];
+
+/// Drops stop words from the terms of another tokenizer, meant for the word
+/// tokenizers
+#[derive(Debug)]
+pub struct Stopwords<T> {
+    inner: T,
+    /// Stop words as the inner tokenizer produces them, e.g. stemmed
+    stopwords: HashSet<String>,
+}
+
+impl<T: Tokenizer> Stopwords<T> {
+    pub fn english(inner: T) -> Self {
+        let stopwords = ENGLISH_STOPWORDS
+            .iter()
+            .flat_map(|word| inner.terms(word))
+            .collect();
+        Self { inner, stopwords }
+    }
+}
+
+impl<T: Tokenizer> Tokenizer for Stopwords<T> {
+    fn tokenize(&self, text: &str, term: &mut dyn FnMut(&str)) {
+        self.inner.tokenize(text, &mut |t| {
+            if !self.stopwords.contains(t) {
+                term(t)
+            }
+        });
+    }
+
+    fn normalization(&self) -> Normalization {
+        self.inner.normalization()
+    }
+}
+
+/// Tokenizer notes are ranked with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum TokenizerKind {
+    /// Character n-grams, which match misspellings and parts of words
+    #[default]
+    Ngrams,
+    /// Whole words
+    Words,
+    /// Words reduced to their English stem, e.g. "running" to "run"
+    Stemmed,
+}
+
+/// Which tokenizer notes are ranked with and how it is set up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenizerConfig {
+    pub kind: TokenizerKind,
+    /// Characters in each n-gram
+    pub ngram_size: usize,
+    /// See [`NGrams::pad_words`]
+    pub pad_words: bool,
+    /// Drop common English words
+    pub stopwords: bool,
+    pub normalization: Normalization,
+    pub segmentation: Segmentation,
+}
+
+impl Default for TokenizerConfig {
+    fn default() -> Self {
+        Self {
+            kind: TokenizerKind::default(),
+            ngram_size: 3,
+            pad_words: false,
+            stopwords: false,
+            normalization: Normalization::default(),
+            segmentation: Segmentation::default(),
+        }
+    }
+}
+
+impl TokenizerConfig {
+    pub fn build(&self) -> Arc<dyn Tokenizer> {
+        let tokenizer: Box<dyn Tokenizer> = match self.kind {
+            TokenizerKind::Ngrams => Box::new(NGrams {
+                n: self.ngram_size,
+                pad_words: self.pad_words,
+                segmentation: self.segmentation,
+                normalization: self.normalization,
+            }),
+            TokenizerKind::Words => Box::new(Words {
+                normalization: self.normalization,
+            }),
+            TokenizerKind::Stemmed => Box::new(Stemmed::english(self.normalization)),
+        };
+        if self.stopwords {
+            Arc::new(Stopwords::english(tokenizer))
+        } else {
+            Arc::from(tokenizer)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_ngram_tokenize_basic() {
+        let result = ngram_tokenize("hello");
+        let expected = vec!["hel", "ell", "llo"];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_ngram_tokenize_short_input() {
+        let result = ngram_tokenize("hi");
+        let expected = vec!["hi"];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_ngram_tokenize_case_insensitive() {
+        let result = ngram_tokenize("Hello");
+        let expected = vec!["hel", "ell", "llo"];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_ngram_tokenize_unicode() {
+        assert_eq!(ngram_tokenize("日本"), vec!["日本"]);
+        assert_eq!(ngram_tokenize("日本語"), vec!["日本語"]);
+        assert!(ngram_tokenize("").is_empty());
+        assert_eq!(ngram_tokenize("Café"), ngram_tokenize("CAFE"));
+        assert_eq!(ngram_tokenize("Straße"), ngram_tokenize("strasse"));
+        assert_eq!(ngram_tokenize("ﬁle"), ngram_tokenize("file"));
+        // A flag is one user-perceived character
+        assert_eq!(ngram_tokenize("🇳🇿ab"), vec!["🇳🇿ab"]);
+
+        let keep = NGrams {
+            normalization: Normalization {
+                strip_diacritics: false,
+            },
+            ..NGrams::trigrams()
+        };
+        assert_ne!(keep.terms("café"), keep.terms("cafe"));
+        assert_eq!(keep.terms("CAFÉ"), keep.terms("café"));
+    }
+
+    #[test]
+    fn test_ngrams_cjk() {
+        let cjk = |s: &str| NGrams::default().terms(s);
+        assert_eq!(cjk("東京都"), vec!["東京", "京都"]);
+        assert_eq!(cjk("日"), vec!["日"]);
+        assert_eq!(cjk("Rust入門"), vec!["rus", "ust", "入門"]);
+        assert_eq!(cjk("カタカナ"), vec!["カタ", "タカ", "カナ"]);
+        // Half width katakana is normalised to full width
+        assert_eq!(cjk("ｶﾀ"), vec!["カタ"]);
+        assert_eq!(NGrams::trigrams().terms("東京都"), vec!["東京都"]);
+    }
+
+    #[test]
+    fn test_ngrams_size_and_padding() {
+        let bigrams = NGrams {
+            n: 2,
+            ..NGrams::default()
+        };
+        assert_eq!(bigrams.terms("Cat"), vec!["ca", "at"]);
+
+        let padded = NGrams {
+            pad_words: true,
+            ..NGrams::default()
+        };
+        assert_eq!(
+            padded.terms("Big cat"),
+            vec![" bi", "big", "ig ", " ca", "cat", "at "]
+        );
+        assert_eq!(padded.terms("京都 go"), vec!["京都", " go", "go "]);
+    }
+
+    #[test]
+    fn test_words_and_stems() {
+        assert_eq!(
+            Whitespace.terms("The  Cat's\that"),
+            vec!["The", "Cat's", "hat"]
+        );
+        assert_eq!(
+            Words::default().terms("The Café, (again)!"),
+            vec!["the", "cafe", "again"]
+        );
+        let stemmed = Stemmed::english(Normalization::default());
+        assert_eq!(
+            stemmed.terms("Running runs; connected"),
+            vec!["run", "run", "connect"]
+        );
+    }
+
+    #[test]
+    fn test_stopwords() {
+        let words = Stopwords::english(Words::default());
+        assert_eq!(words.terms("The plan for this week"), vec!["plan", "week"]);
+        // Stop words are compared in the form the tokenizer gives them
+        let stemmed = Stopwords::english(Stemmed::english(Normalization::default()));
+        assert_eq!(stemmed.terms("Does it rain"), vec!["rain"]);
+    }
+
+    #[test]
+    fn test_build() {
+        let config = TokenizerConfig {
+            kind: TokenizerKind::Stemmed,
+            stopwords: true,
+            ..TokenizerConfig::default()
+        };
+        assert_eq!(config.build().terms("The gardens"), vec!["garden"]);
+        assert_eq!(
+            TokenizerConfig::default().build().terms("Garden"),
+            ngram_tokenize("Garden")
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn prop_trigrams_cover_graphemes(s in "\\PC{0,40}") {
+            let normalized = normalize(&s, Normalization::default());
+            let graphemes = normalized.graphemes(true).count();
+            let tokens = ngram_tokenize(&s);
+            prop_assert_eq!(tokens.len(), if graphemes < 3 { graphemes.min(1) } else { graphemes - 2 });
+            for token in &tokens {
+                prop_assert!(!token.is_empty());
+                prop_assert!(token.graphemes(true).count() <= 3);
+            }
+        }
+
+        #[test]
+        fn prop_normalize_is_idempotent(s in "\\PC{0,40}", strip_diacritics: bool) {
+            let normalization = Normalization { strip_diacritics };
+            let once = normalize(&s, normalization);
+            prop_assert_eq!(normalize(&once, normalization), once);
+        }
+
+        #[test]
+        fn prop_tokens_ignore_case(s in "[a-zA-Zà-öø-ÿ ]{0,30}") {
+            prop_assert_eq!(ngram_tokenize(&s.to_uppercase()), ngram_tokenize(&s));
+        }
+
+        #[test]
+        fn prop_cjk_segmentation_keeps_other_text(s in "\\PC{0,40}") {
+            prop_assume!(!normalize(&s, Normalization::default()).chars().any(is_cjk));
+            prop_assert_eq!(NGrams::default().terms(&s), ngram_tokenize(&s));
+        }
+
+        #[test]
+        fn prop_cjk_runs_are_bigrams(s in "[\u{4E00}-\u{9FFF}\u{3041}-\u{3096}]{2,20}") {
+            let terms = NGrams::default().terms(&s);
+            prop_assert_eq!(terms.len(), s.chars().count() - 1);
+            prop_assert!(terms.iter().all(|term| term.chars().count() == 2));
+        }
+
+        #[test]
+        fn prop_tokens_ignore_accents(s in "[a-z]{1,20}", at in 0usize..20) {
+            let at = at % s.len() + 1;
+            let accented = format!("{}\u{0301}{}", &s[..at], &s[at..]);
+            prop_assert_eq!(ngram_tokenize(&accented), ngram_tokenize(&s));
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Corrections for mistyped query words, so "meeitng" still finds "Meeting".
+
+use std::collections::HashMap;
+
+/// Query words shorter than this are not corrected, too many words are an
+/// edit or two away from them
+const MIN_WORD_CHARS: usize = 4;
+
+/// Corrections tried for each mistyped word
+const MAX_CORRECTIONS: usize = 3;
+
+/// Edits allowed between a query word and a word it may have meant
+pub fn max_typos(chars: usize) -> usize {
+    match chars {
+        0..MIN_WORD_CHARS => 0,
+        MIN_WORD_CHARS..=5 => 1,
+        _ => 2,
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    word: String,
+    chars: Vec<char>,
+    /// Titles the word is in
+    count: u32,
+}
+
+/// Words of the note titles, which mistyped words are corrected to
+#[derive(Debug, Default)]
+pub struct Vocabulary {
+    /// Sorted by word
+    entries: Vec<Entry>,
+}
+
+impl Vocabulary {
+    /// A vocabulary of words and the number of titles each is in
+    pub fn new(counts: HashMap<String, u32>) -> Self {
+        let mut entries: Vec<Entry> = counts
+            .into_iter()
+            .map(|(word, count)| Entry {
+                chars: word.chars().collect(),
+                word,
+                count,
+            })
+            .collect();
+        entries.sort_by(|a, b| a.word.cmp(&b.word));
+        Self { entries }
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.entries
+            .binary_search_by(|entry| entry.word.as_str().cmp(word))
+            .is_ok()
+    }
+
+    /// Words `word` may have been a typo of, with their distance from it,
+    /// closest and then most common first. None for words in the vocabulary.
+    pub fn corrections(&self, word: &str) -> Vec<(&str, usize)> {
+        let chars: Vec<char> = word.chars().collect();
+        let max = max_typos(chars.len());
+        if max == 0 || self.contains(word) {
+            return Vec::new();
+        }
+
+        let mut corrections: Vec<(&Entry, usize)> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.chars.len().abs_diff(chars.len()) <= max)
+            .filter_map(|entry| Some((entry, damerau_levenshtein(&chars, &entry.chars, max)?)))
+            .collect();
+        corrections.sort_by(|(a, a_distance), (b, b_distance)| {
+            a_distance.cmp(b_distance).then(b.count.cmp(&a.count))
+        });
+        corrections
+            .into_iter()
+            .take(MAX_CORRECTIONS)
+            .map(|(entry, distance)| (entry.word.as_str(), distance))
+            .collect()
+    }
+}
+
+/// Restricted Damerau–Levenshtein distance (optimal string alignment):
+/// inserting, deleting or substituting a character or swapping two adjacent
+/// ones each count as one edit. None when it is more than `max`.
+pub fn damerau_levenshtein(a: &[char], b: &[char], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+
+    // Distances between a prefix of `a` and every prefix of `b`, for the
+    // prefixes one and two characters shorter and the current one
+    let mut before_previous: Vec<usize> = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current: Vec<usize> = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        current[0] = i;
+        let mut row_min = current[0];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(before_previous[j - 2] + 1);
+            }
+            current[j] = distance;
+            row_min = row_min.min(distance);
+        }
+        // Distances never shrink further down the table
+        if row_min > max {
+            return None;
+        }
+        std::mem::swap(&mut before_previous, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    Some(previous[b.len()]).filter(|&distance| distance <= max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(a: &str, b: &str) -> Option<usize> {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        damerau_levenshtein(&a, &b, 3)
+    }
+
+    #[test]
+    fn test_damerau_levenshtein() {
+        assert_eq!(distance("meeting", "meeting"), Some(0));
+        assert_eq!(distance("meeitng", "meeting"), Some(1)); // swap
+        assert_eq!(distance("meetng", "meeting"), Some(1)); // deletion
+        assert_eq!(distance("meetiing", "meeting"), Some(1)); // insertion
+        assert_eq!(distance("meetimg", "meeting"), Some(1)); // substitution
+        assert_eq!(distance("emetign", "meeting"), Some(2));
+        assert_eq!(distance("café", "cafe"), Some(1));
+        assert_eq!(distance("", "abc"), Some(3));
+        assert_eq!(distance("meeting", "garden"), None);
+        assert_eq!(distance("ab", "abcdef"), None);
+    }
+
+    #[test]
+    fn test_corrections() {
+        let vocabulary = Vocabulary::new(HashMap::from([
+            ("meeting".to_string(), 3),
+            ("meetings".to_string(), 1),
+            ("greeting".to_string(), 1),
+            ("garden".to_string(), 2),
+        ]));
+        assert_eq!(
+            vocabulary.corrections("meeitng"),
+            vec![("meeting", 1), ("meetings", 2)]
+        );
+        assert_eq!(vocabulary.corrections("gerden"), vec![("garden", 1)]);
+        // Known and short words are left alone
+        assert!(vocabulary.corrections("meeting").is_empty());
+        assert!(vocabulary.corrections("gdn").is_empty());
+    }
+}
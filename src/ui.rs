@@ -1,89 +1,463 @@
-use crate::bm25::bm25_trigram;
-use crate::list::SelectableList;
-use crate::note::Note;
+use crate::bm25::{FieldWeights, NoteIndex};
+use crate::dates::{DateField, DateRange};
+use crate::editor::{EditorAction, NoteEditor, EDIT_BODY_ID, EDIT_TITLE_ID};
+use crate::export::export;
+use crate::folder::Folder;
+use crate::history::History;
+use crate::list::{SelectableList, QUICK_KEYS};
+use crate::markdown::MarkdownViewer;
+use crate::mode::{consume_key_and_text, Mode};
+use crate::note::{Include, Note, SaveOutcome};
+use crate::open::{OpenAction, Opener};
+use crate::pins::Pins;
+use crate::query::Query;
+use crate::sort::{sort_notes, SortContext, SortMode};
+use crate::tokenizer::TokenizerConfig;
 use eframe::egui;
+use ordered_float::OrderedFloat;
 use rand::thread_rng;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::path::PathBuf;
 
-const FILTER_ID: &str = "title_filter_id";
-const BODY_FILTER_ID: &str = "body_filter_id";
+const QUERY_ID: &str = "query_id";
 const LIST_ID: &str = "notes_list_id";
 
-pub fn run(database: String) -> eframe::Result {
+/// Recently picked notes shown above the list
+const RECENT_NOTES: usize = 5;
+
+/// Shortcuts listed in the status bar in normal mode
+const NORMAL_MODE_HELP: &str =
+    "/: search  j/k: move  gg/G: top/bottom  e: edit  x: toggle to-do  p: pin  1-9: pick";
+
+/// Placeholder of the empty query box, a reminder of the query syntax
+const QUERY_HINT: &str =
+    "words b:body nb:notebook tag:tag is:todo is:overdue updated:>2024-01-01 -exclude";
+
+/// Settings for the picker that come from the command line
+pub struct UiOptions {
+    pub opener: Opener,
+    /// Whether actions that modify the database are enabled
+    pub allow_write: bool,
+    /// Hidden kinds of notes to list anyway
+    pub include: Include,
+    /// Where the export action writes notes to
+    pub export_dir: PathBuf,
+    /// Notebook new notes are created in, see [`Folder::resolve`]
+    pub notebook: Option<String>,
+    /// Notes picked before, used to rank the list
+    pub history: History,
+    /// Notes shown above the list while nothing filters it
+    pub pins: Pins,
+    /// Importance of title and body matches when ranking by the query
+    pub weights: FieldWeights,
+    /// How note text and the query are split into terms when ranking
+    pub tokenizer: TokenizerConfig,
+    /// Ignore the state saved by the last session
+    pub fresh: bool,
+    /// Filters and selection to start with instead of the last session's
+    pub start: Option<Session>,
+    /// Pick the only match without opening the window, like fzf's `--select-1`
+    pub select_1: bool,
+    /// Exit without opening the window when nothing matches, like fzf's `--exit-0`
+    pub exit_0: bool,
+}
+
+/// Why the picker finished before opening its window
+#[derive(Debug, PartialEq, Eq)]
+pub enum Skipped {
+    /// Link to the only match
+    Picked(String),
+    NoMatches,
+}
+
+/// Where the last session left off, restored on the next launch.
+///
+/// Panel widths and the window geometry are kept by eframe itself.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub query: String,
+    pub sort: SortMode,
+    pub date_range: DateRange,
+    /// Selected note, selected again if it is still listed
+    pub selected_id: Option<String>,
+}
+
+const WINDOW_SIZE: [f32; 2] = [640.0, 480.0];
+
+pub fn run(database: String, mut options: UiOptions) -> eframe::Result {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
-    let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size([640.0, 480.0]),
+    let start = options.start.take();
+    let restore_last = start.is_none() && !options.fresh;
+    let fresh = options.fresh;
+    let mut app = MyApp::new(database, options);
+    if let Some(start) = start {
+        app.restore(start);
+    }
+    match app.skip_window() {
+        Some(Skipped::Picked(link)) => {
+            println!("{link}");
+            return Ok(());
+        }
+        // Nothing printed and a failure status, as fzf does
+        Some(Skipped::NoMatches) => std::process::exit(1),
+        None => {}
+    }
+
+    let native_options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size(WINDOW_SIZE)
+            // Names the directory the session is saved in
+            .with_app_id("chalsedony_selector"),
         ..Default::default()
     };
     eframe::run_native(
         "My egui App",
-        options,
+        native_options,
         Box::new(|cc| {
             egui_extras::install_image_loaders(&cc.egui_ctx);
-            Ok(Box::new(MyApp::new(database)))
+            if fresh {
+                // eframe restores these before the app exists, undo it
+                cc.egui_ctx.memory_mut(|mem| *mem = egui::Memory::default());
+                cc.egui_ctx
+                    .send_viewport_cmd(egui::ViewportCommand::InnerSize(WINDOW_SIZE.into()));
+            }
+            if restore_last {
+                if let Some(session) = cc
+                    .storage
+                    .and_then(|storage| eframe::get_value(storage, eframe::APP_KEY))
+                {
+                    app.restore(session);
+                }
+            }
+            Ok(Box::new(app))
         }),
     )
 }
 
-struct MyApp {
-    title_filter: String,
-    body_filter: String,
+pub struct MyApp {
+    query: String,
+    /// Why the query could not be used, the list is left as it was
+    query_error: Option<String>,
     initialization: bool,
     list: SelectableList,
+    viewer: MarkdownViewer,
     database: String,
+    options: UiOptions,
+    /// Message shown in the status bar, e.g. the result of the last action
+    status: Option<String>,
+    /// Link to the picked or created note, printed before exiting
+    accepted: Option<String>,
+    sort: SortMode,
+    date_range: DateRange,
+    /// Loaded the first time a sort mode needs it
+    sort_context: Option<SortContext>,
+    /// Built the first time a filter is typed, dropped when a note is saved
+    note_index: Option<NoteIndex>,
 }
 
 impl MyApp {
-    fn new(database: String) -> Self {
+    pub fn new(database: String, options: UiOptions) -> Self {
         let _rng = thread_rng();
-        Self {
-            title_filter: "".to_owned(),
-            body_filter: String::new(),
-            list: SelectableList::new(Note::load_all(&database).expect("unable to load database")),
+        let notes = Note::load_all(&database, options.include).expect("unable to load database");
+        let mut app = Self {
+            query: String::new(),
+            query_error: None,
+            list: SelectableList::new(Vec::new()),
             initialization: true,
+            viewer: MarkdownViewer::new(&database),
             database,
+            options,
+            status: None,
+            accepted: None,
+            sort: SortMode::default(),
+            date_range: DateRange::default(),
+            sort_context: None,
+            note_index: None,
+        };
+        let notes = app.rank_notes(&Query::default(), notes);
+        app.list = SelectableList::new(notes);
+        app.update_quick_notes();
+        app
+    }
+
+    /// Replace the query and filter the list, as typing into the query box does
+    pub fn set_query(&mut self, query: &str) {
+        self.query = query.to_string();
+        self.update_filtered_notes();
+    }
+
+    /// Notes in the list, in the order shown
+    pub fn notes(&self) -> &[Note] {
+        &self.list.items
+    }
+
+    /// The filters and selection, to be restored by [`MyApp::restore`]
+    pub fn session(&self) -> Session {
+        Session {
+            query: self.query.clone(),
+            sort: self.sort,
+            date_range: self.date_range,
+            selected_id: self
+                .list
+                .selected_item
+                .and_then(|i| self.list.items.get(i))
+                .map(|note| note.id.clone()),
+        }
+    }
+
+    /// Finish without showing the window, if `--select-1` or `--exit-0` apply
+    /// to the listed notes. A pick is recorded as if made in the window.
+    pub fn skip_window(&mut self) -> Option<Skipped> {
+        match self.list.items.as_slice() {
+            [note] if self.options.select_1 => {
+                let note = note.clone();
+                self.accept(&note);
+                self.accepted.clone().map(Skipped::Picked)
+            }
+            [] if self.options.exit_0 => Some(Skipped::NoMatches),
+            _ => None,
+        }
+    }
+
+    /// Continue where a previous session left off
+    pub fn restore(&mut self, session: Session) {
+        self.query = session.query;
+        self.sort = session.sort;
+        self.date_range = session.date_range;
+        self.update_filtered_notes();
+        if let Some(id) = session.selected_id {
+            self.list.select_id(&id);
         }
     }
 }
 
 impl MyApp {
     fn update_filtered_notes(&mut self) {
-        // Get base set of notes
-        let mut sorted_notes = if self.body_filter.is_empty() {
-            // If body filter is empty, load all notes from database
-            Note::load_all(&self.database).unwrap_or_else(|_| Vec::new())
+        let found = Query::parse(&self.query)
+            .map_err(|e| e.to_string())
+            .and_then(|query| {
+                let notes = Note::find(&self.database, &query, self.options.include)
+                    .map_err(|e| e.to_string())?;
+                Ok((query, notes))
+            });
+        let (query, mut notes) = match found {
+            Ok(found) => found,
+            Err(e) => {
+                self.query_error = Some(e);
+                return;
+            }
+        };
+        self.query_error = None;
+        notes.retain(|note| self.date_range.contains(note));
+        let sorted_notes = self.rank_notes(&query, notes);
+
+        // Update the list with filtered notes, an edit in progress and the
+        // selected note, while it is still listed, carry over
+        let editor = self.list.editor.take();
+        let selected_id = self.list.selected().map(|note| note.id.clone());
+        self.list = SelectableList::new(sorted_notes);
+        self.list.editor = editor;
+        if let Some(id) = selected_id {
+            self.list.select_id(&id);
+        }
+        self.update_quick_notes();
+    }
+
+    /// Fill the pinned and recent section, it is only shown while nothing
+    /// filters the list, which then holds every note to look them up in
+    fn update_quick_notes(&mut self) {
+        self.list.pinned.clear();
+        self.list.recent.clear();
+        if !self.query.trim().is_empty() || !self.date_range.is_unbounded() {
+            return;
+        }
+
+        let find = |id: &String| self.list.items.iter().find(|note| &note.id == id).cloned();
+        let pins = &self.options.pins;
+        let pinned: Vec<Note> = pins.ids().iter().filter_map(find).collect();
+        let recent: Vec<Note> = self
+            .options
+            .history
+            .recent(RECENT_NOTES + pins.ids().len())
+            .iter()
+            .filter(|id| !pins.contains(id))
+            .take(RECENT_NOTES)
+            .filter_map(find)
+            .collect();
+        self.list.pinned = pinned;
+        self.list.recent = recent;
+    }
+
+    /// Order notes by the query, pick history and sort mode
+    fn rank_notes(&mut self, query: &Query, mut notes: Vec<Note>) -> Vec<Note> {
+        let frecency = self
+            .options
+            .history
+            .frecency(chrono::Utc::now().timestamp());
+        let frecency = |note: &Note| OrderedFloat(frecency.get(&note.id).copied().unwrap_or(0.0));
+
+        if query.has_ranking_terms() {
+            // Often picked notes win ties
+            let index = self.note_index.get_or_insert_with(|| {
+                NoteIndex::load(
+                    &self.database,
+                    self.options.include,
+                    self.options.tokenizer.build(),
+                )
+                .unwrap_or_else(|_| NoteIndex::new(&[], self.options.tokenizer.build()))
+            });
+            query.rank(&mut notes, index, self.options.weights, frecency);
         } else {
-            // If we have a body filter, use FTS search
-            Note::search(&self.database, &self.body_filter).unwrap_or_else(|_| Vec::new())
+            // Nothing to rank by, often picked notes first
+            notes.sort_by_key(|note| Reverse(frecency(note)));
+        }
+
+        if self.sort.needs_context() && self.sort_context.is_none() {
+            self.sort_context = Some(SortContext::load(&self.database).unwrap_or_default());
+        }
+        let no_context = SortContext::default();
+        sort_notes(
+            &mut notes,
+            self.sort,
+            self.sort_context.as_ref().unwrap_or(&no_context),
+        );
+        notes
+    }
+
+    /// Pick the note selected in the list, its link is printed on exit
+    fn accept_selected(&mut self) {
+        if let Some(note) = self.list.selected().cloned() {
+            self.accept(&note);
+        }
+    }
+
+    fn accept(&mut self, note: &Note) {
+        self.record_pick(&note.id);
+        self.accepted = Some(note.markdown_link());
+    }
+
+    /// Pin the previewed note above the list, or unpin it
+    fn toggle_pin(&mut self) {
+        let Some(note) = self.list.previewed().cloned() else {
+            return;
         };
+        self.status = Some(match self.options.pins.toggle(&note.id) {
+            Ok(true) => format!("Pinned \"{}\"", note.title),
+            Ok(false) => format!("Unpinned \"{}\"", note.title),
+            Err(e) => format!("Unable to save pins: {e}"),
+        });
+        self.update_quick_notes();
+    }
 
-        // Then apply title filter if present
-        if !self.title_filter.is_empty() {
-            let titles: Vec<String> = sorted_notes.iter().map(|note| note.title.clone()).collect();
+    fn record_pick(&mut self, note_id: &str) {
+        let now = chrono::Utc::now().timestamp();
+        if let Err(e) = self.options.history.record(note_id, now) {
+            eprintln!("Unable to record history: {e}");
+        }
+    }
+}
+
+impl MyApp {
+    fn open_previewed(&mut self, action: OpenAction) {
+        let Some(note) = self.list.previewed() else {
+            return;
+        };
+        self.status = Some(match self.options.opener.open(action, note) {
+            Ok(_) => format!("Opened \"{}\"", note.title),
+            Err(e) => format!("Unable to open \"{}\": {e}", note.title),
+        });
+    }
 
-            let sorted_titles = bm25_trigram(&titles, &self.title_filter);
+    /// Title for a note created from the query, unless a listed note already
+    /// has exactly that title
+    fn new_title(&self) -> Option<String> {
+        let title = Query::parse(&self.query).ok()?.text();
+        let exists = self
+            .list
+            .items
+            .iter()
+            .any(|note| note.title.trim().to_lowercase() == title.to_lowercase());
+        (!title.is_empty() && !exists).then_some(title)
+    }
 
-            // Reorder notes based on sorted titles
-            let mut title_sorted_notes = Vec::new();
-            for title in sorted_titles {
-                if let Some(note) = sorted_notes.iter().find(|note| note.title == title) {
-                    title_sorted_notes.push(note.clone());
+    /// Create a note titled after the words of the query and pick it
+    fn create_note(&mut self) {
+        let title = Query::parse(&self.query)
+            .map(|query| query.text())
+            .unwrap_or_default();
+        let title = title.as_str();
+        if !self.options.allow_write {
+            self.status = Some("Start with --allow-write to create notes".to_string());
+            return;
+        }
+        if title.is_empty() {
+            self.status = Some("Type a title into the query first".to_string());
+            return;
+        }
+
+        let created =
+            Folder::resolve(&self.database, self.options.notebook.as_deref()).and_then(|folder| {
+                match folder {
+                    Some(folder) => Note::create(&self.database, title, "", &folder.id).map(Some),
+                    None => Ok(None),
                 }
+            });
+        match created {
+            Ok(Some(note)) => {
+                self.record_pick(&note.id);
+                self.accepted = Some(note.markdown_link());
             }
-            sorted_notes = title_sorted_notes;
+            Ok(None) => self.status = Some("No notebook to create the note in".to_string()),
+            Err(e) => self.status = Some(format!("Unable to create note: {e}")),
         }
-
-        // Update the list with filtered notes
-        self.list = SelectableList::new(sorted_notes);
     }
-}
 
-impl eframe::App for MyApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        if ctx.input(|i| i.key_pressed(egui::Key::S) && i.modifiers.ctrl) {
-            ctx.memory_mut(|mem| mem.request_focus(egui::Id::new(FILTER_ID)));
+    fn handle_shortcuts(&mut self, ctx: &egui::Context, mode: Mode) {
+        if mode == Mode::Normal
+            && consume_key_and_text(ctx, egui::Modifiers::NONE, egui::Key::Slash, "/")
+        {
+            ctx.memory_mut(|mem| mem.request_focus(egui::Id::new(QUERY_ID)));
+        }
+        if mode == Mode::Normal
+            && consume_key_and_text(ctx, egui::Modifiers::NONE, egui::Key::E, "e")
+        {
+            self.start_editing();
+        }
+        if mode == Mode::Normal
+            && consume_key_and_text(ctx, egui::Modifiers::NONE, egui::Key::X, "x")
+        {
+            self.toggle_completed();
+        }
+        if mode == Mode::Normal
+            && consume_key_and_text(ctx, egui::Modifiers::NONE, egui::Key::P, "p")
+        {
+            self.toggle_pin();
+        }
+        if mode == Mode::Normal {
+            let number_keys = [
+                egui::Key::Num1,
+                egui::Key::Num2,
+                egui::Key::Num3,
+                egui::Key::Num4,
+                egui::Key::Num5,
+                egui::Key::Num6,
+                egui::Key::Num7,
+                egui::Key::Num8,
+                egui::Key::Num9,
+            ];
+            for (n, key) in number_keys.into_iter().enumerate().take(QUICK_KEYS) {
+                let text = (n + 1).to_string();
+                if consume_key_and_text(ctx, egui::Modifiers::NONE, key, &text) {
+                    if let Some(note) = self.list.quick_note(n).cloned() {
+                        self.accept(&note);
+                    }
+                }
+            }
         }
-        if ctx.input(|i| i.key_pressed(egui::Key::B) && i.modifiers.ctrl) {
-            ctx.memory_mut(|mem| mem.request_focus(egui::Id::new(BODY_FILTER_ID)));
+        if ctx.input(|i| i.key_pressed(egui::Key::S) && i.modifiers.ctrl) {
+            ctx.memory_mut(|mem| mem.request_focus(egui::Id::new(QUERY_ID)));
         }
         if ctx.input(|i| i.key_pressed(egui::Key::L) && i.modifiers.ctrl) {
             ctx.memory_mut(|mem| mem.request_focus(egui::Id::new(LIST_ID)));
@@ -91,10 +465,17 @@ impl eframe::App for MyApp {
         if ctx.input(|i| i.key_pressed(egui::Key::C) && i.modifiers.ctrl) {
             self.list.copy_selected_to_clipboard(ctx);
         }
-        if ctx.input(|i| i.key_pressed(egui::Key::Enter)) {
-            self.list.print_selected();
-            // Automatically close
-            std::process::exit(0);
+        if ctx.input(|i| i.key_pressed(egui::Key::Enter) && i.modifiers.command) {
+            self.create_note();
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::Enter) && !i.modifiers.command) {
+            self.accept_selected();
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::O) && i.modifiers.ctrl) {
+            self.open_previewed(OpenAction::Joplin);
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::E) && i.modifiers.ctrl) {
+            self.open_previewed(OpenAction::Editor);
         }
         if ctx.input(|i| i.key_pressed(egui::Key::N) && i.modifiers.ctrl) {
             self.list.move_selection(crate::list::Direction::Down);
@@ -102,13 +483,133 @@ impl eframe::App for MyApp {
         if ctx.input(|i| i.key_pressed(egui::Key::P) && i.modifiers.ctrl) {
             self.list.move_selection(crate::list::Direction::Up);
         }
+    }
+
+    /// Write notes to the export directory as Markdown, see [`export`]
+    fn export_notes(&mut self, notes: &[Note]) {
+        let dir = &self.options.export_dir;
+        self.status = Some(match export(&self.database, notes, dir) {
+            Ok(summary) if summary.missing_resources.is_empty() => format!(
+                "Exported {} notes and {} resources to {}",
+                summary.notes,
+                summary.resources,
+                dir.display()
+            ),
+            Ok(summary) => format!(
+                "Exported {} notes to {}, {} resource files were missing",
+                summary.notes,
+                dir.display(),
+                summary.missing_resources.len()
+            ),
+            Err(e) => format!("Unable to export: {e}"),
+        });
+    }
+
+    /// Check off the previewed to-do, or reopen it
+    fn toggle_completed(&mut self) {
+        let Some(note) = self.list.previewed().filter(|note| note.is_todo) else {
+            return;
+        };
+        if !self.options.allow_write {
+            self.status = Some("Start with --allow-write to complete to-dos".to_string());
+            return;
+        }
+        match note.set_completed(&self.database, note.todo_completed.is_none()) {
+            Ok(updated) => {
+                self.status = Some(format!(
+                    "{} \"{}\"",
+                    if updated.todo_completed.is_some() {
+                        "Completed"
+                    } else {
+                        "Reopened"
+                    },
+                    updated.title
+                ));
+                self.list.replace_note(updated);
+            }
+            Err(e) => self.status = Some(format!("Unable to update to-do: {e}")),
+        }
+    }
+
+    /// Open the previewed note in the editor, freshly loaded so conflicts are detected
+    fn start_editing(&mut self) {
+        if !self.options.allow_write {
+            self.status = Some("Start with --allow-write to edit notes".to_string());
+            return;
+        }
+        let Some(note_id) = self.list.previewed().map(|note| note.id.clone()) else {
+            return;
+        };
+        match Note::get_by_id(&self.database, &note_id) {
+            Ok(note) => self.list.editor = Some(NoteEditor::new(note)),
+            Err(e) => self.status = Some(format!("Unable to load note: {e}")),
+        }
+    }
+
+    fn handle_editor_action(&mut self, action: EditorAction) {
+        let Some(editor) = &mut self.list.editor else {
+            return;
+        };
+        if action == EditorAction::Cancel {
+            self.list.editor = None;
+            return;
+        }
+
+        let force = action == EditorAction::Overwrite;
+        let outcome = editor
+            .original
+            .save(&self.database, &editor.title, &editor.body, force);
+        match outcome {
+            Ok(SaveOutcome::Saved(note)) => {
+                self.status = Some(format!("Saved \"{}\"", note.title));
+                self.list.finish_editing(note);
+                self.note_index = None;
+            }
+            Ok(SaveOutcome::Conflict) => {
+                editor.conflict = true;
+                self.status = Some("Not saved, the note was changed elsewhere".to_string());
+            }
+            Ok(SaveOutcome::Deleted) => {
+                self.status = Some("Not saved, the note no longer exists".to_string());
+            }
+            Err(e) => self.status = Some(format!("Unable to save note: {e}")),
+        }
+    }
+}
+
+impl MyApp {
+    /// Draw a frame and act on its input
+    fn show(&mut self, ctx: &egui::Context) {
+        let mode = Mode::from_focus(ctx, &[QUERY_ID, EDIT_TITLE_ID, EDIT_BODY_ID]);
+
+        // While editing, keys go to the editor, which handles its own shortcuts
+        if !self.list.is_editing() {
+            self.handle_shortcuts(ctx, mode);
+        }
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.monospace(mode.label());
+                ui.separator();
+                ui.label(match mode {
+                    Mode::Insert => "Esc: normal mode",
+                    Mode::Normal => NORMAL_MODE_HELP,
+                });
+                if let Some(status) = &self.status {
+                    ui.separator();
+                    ui.label(status);
+                }
+            });
+        });
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("Link Creator");
             ui.horizontal(|ui| {
-                let _title_filter = ui.label("Title Filter: ");
+                ui.label("Query: ");
                 let edit = ui.add(
-                    egui::TextEdit::singleline(&mut self.title_filter).id(egui::Id::new(FILTER_ID)),
+                    egui::TextEdit::singleline(&mut self.query)
+                        .id(egui::Id::new(QUERY_ID))
+                        .hint_text(QUERY_HINT)
+                        .desired_width(f32::INFINITY),
                 );
 
                 if edit.changed() {
@@ -120,21 +621,494 @@ impl eframe::App for MyApp {
                     self.initialization = false;
                 }
             });
+            if let Some(error) = &self.query_error {
+                ui.colored_label(ui.visuals().warn_fg_color, error);
+            }
             ui.horizontal(|ui| {
-                let _body_filter = ui.label("Body Filter: ");
-                let body_edit = ui.add(
-                    egui::TextEdit::singleline(&mut self.body_filter)
-                        .id(egui::Id::new(BODY_FILTER_ID)),
-                );
-
-                if body_edit.changed() {
+                ui.label("Sort: ");
+                let previous = self.sort;
+                egui::ComboBox::from_id_salt("sort_mode")
+                    .selected_text(self.sort.label())
+                    .show_ui(ui, |ui| {
+                        for mode in SortMode::ALL {
+                            ui.selectable_value(&mut self.sort, mode, mode.label());
+                        }
+                    });
+                if self.sort != previous {
                     self.update_filtered_notes();
                 }
             });
+            if date_range_ui(ui, &mut self.date_range) {
+                self.update_filtered_notes();
+            }
+            ui.menu_button("Export", |ui| {
+                if let Some(note) = self.list.previewed().cloned() {
+                    if ui.button("Previewed note").clicked() {
+                        self.export_notes(&[note]);
+                        ui.close_menu();
+                    }
+                }
+                let listed = self.list.items.clone();
+                if ui
+                    .button(format!("All {} listed notes", listed.len()))
+                    .clicked()
+                {
+                    self.export_notes(&listed);
+                    ui.close_menu();
+                }
+            })
+            .response
+            .on_hover_text(format!(
+                "Write Markdown files to {}",
+                self.options.export_dir.display()
+            ));
+
+            if let Some(title) = self.new_title() {
+                let create = ui
+                    .add_enabled(
+                        self.options.allow_write,
+                        egui::Button::new(format!("Create note \"{title}\" (Ctrl+Enter)")),
+                    )
+                    .on_disabled_hover_text("Start with --allow-write to create notes");
+                if create.clicked() {
+                    self.create_note();
+                }
+            }
 
             ui.separator();
             ui.heading("Items List");
-            self.list.show(ctx, ui, LIST_ID);
+            let response = self.list.show(ctx, ui, LIST_ID, mode, &mut self.viewer);
+            if response.edit_requested {
+                self.start_editing();
+            }
+            if response.toggle_completed {
+                self.toggle_completed();
+            }
+            if let Some(action) = response.editor_action {
+                self.handle_editor_action(action);
+            }
+            if let Some(note_id) = response.clicked_link {
+                // Links to resources rather than notes don't resolve and are ignored
+                if let Ok(note) = Note::get_by_id(&self.database, &note_id) {
+                    self.list.navigate_to(note);
+                }
+            }
+            if let Some(note_id) = self.list.backlinks_pending() {
+                let backlinks = Note::backlinks(&self.database, &note_id, self.options.include)
+                    .unwrap_or_else(|_| Vec::new());
+                self.list.set_backlinks(note_id, backlinks);
+            }
         });
     }
 }
+
+impl eframe::App for MyApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.show(ctx);
+
+        if let Some(link) = &self.accepted {
+            println!("{link}");
+            // Automatically close
+            std::process::exit(0);
+        }
+    }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, eframe::APP_KEY, &self.session());
+    }
+}
+
+/// Controls for the days notes must be created or updated in, true when changed
+fn date_range_ui(ui: &mut egui::Ui, range: &mut DateRange) -> bool {
+    let previous = *range;
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_salt("date_field")
+            .selected_text(range.field.label())
+            .show_ui(ui, |ui| {
+                for field in [DateField::Updated, DateField::Created] {
+                    ui.selectable_value(&mut range.field, field, field.label());
+                }
+            });
+
+        let today = chrono::Local::now().date_naive();
+        for (label, date) in [("since", &mut range.since), ("until", &mut range.until)] {
+            let mut bounded = date.is_some();
+            if ui.checkbox(&mut bounded, label).changed() {
+                *date = bounded.then_some(today);
+            }
+            if let Some(date) = date {
+                ui.add(egui_extras::DatePickerButton::new(date).id_salt(label));
+            }
+        }
+
+        if !range.is_unbounded() && ui.button("Any time").clicked() {
+            range.since = None;
+            range.until = None;
+        }
+    });
+    *range != previous
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::Fixture;
+
+    /// The picker on a fixture database, driven by synthetic input without a window
+    struct Harness {
+        ctx: egui::Context,
+        app: MyApp,
+        _fixture: Fixture,
+    }
+
+    impl Harness {
+        fn new(fixture: Fixture, allow_write: bool) -> Self {
+            let options = UiOptions {
+                opener: Opener::new(None, None),
+                allow_write,
+                include: Include::default(),
+                export_dir: std::env::temp_dir(),
+                notebook: None,
+                history: History::default(),
+                pins: Pins::default(),
+                weights: FieldWeights::default(),
+                tokenizer: TokenizerConfig::default(),
+                fresh: false,
+                start: None,
+                select_1: true,
+                exit_0: true,
+            };
+            let mut harness = Self {
+                ctx: egui::Context::default(),
+                app: MyApp::new(fixture.path().to_string(), options),
+                _fixture: fixture,
+            };
+            // The query box takes focus in the first frame and registers which
+            // keys it keeps in the second, as the repaints of a window would
+            harness.frame(Vec::new());
+            harness.frame(Vec::new());
+            harness
+        }
+
+        fn frame(&mut self, events: Vec<egui::Event>) {
+            let modifiers = events
+                .iter()
+                .find_map(|event| match event {
+                    egui::Event::Key { modifiers, .. } => Some(*modifiers),
+                    _ => None,
+                })
+                .unwrap_or_default();
+            let input = egui::RawInput {
+                modifiers,
+                screen_rect: Some(egui::Rect::from_min_size(
+                    egui::Pos2::ZERO,
+                    egui::vec2(1024.0, 768.0),
+                )),
+                events,
+                ..Default::default()
+            };
+            let _ = self.ctx.run(input, |ctx| self.app.show(ctx));
+        }
+
+        fn key(key: egui::Key, modifiers: egui::Modifiers) -> egui::Event {
+            egui::Event::Key {
+                key,
+                physical_key: None,
+                pressed: true,
+                repeat: false,
+                modifiers,
+            }
+        }
+
+        fn press(&mut self, key: egui::Key, modifiers: egui::Modifiers) {
+            self.frame(vec![Self::key(key, modifiers)]);
+        }
+
+        /// A printable key, sent as both a key press and text like a real keyboard does
+        fn type_key(&mut self, key: egui::Key, text: &str) {
+            let modifiers = if text.chars().all(char::is_uppercase) {
+                egui::Modifiers::SHIFT
+            } else {
+                egui::Modifiers::NONE
+            };
+            self.frame(vec![
+                Self::key(key, modifiers),
+                egui::Event::Text(text.to_string()),
+            ]);
+        }
+
+        fn type_text(&mut self, text: &str) {
+            self.frame(vec![egui::Event::Text(text.to_string())]);
+        }
+
+        fn titles(&self) -> Vec<&str> {
+            self.app
+                .list
+                .items
+                .iter()
+                .map(|n| n.title.as_str())
+                .collect()
+        }
+
+        fn previewed_title(&self) -> Option<&str> {
+            self.app.list.previewed().map(|n| n.title.as_str())
+        }
+    }
+
+    fn fixture() -> (Fixture, Vec<String>) {
+        let mut fixture = Fixture::temporary().unwrap();
+        let folder = fixture.folder("Work", "").unwrap();
+        let ids = ["Groceries", "Meeting notes", "Garden plan"]
+            .iter()
+            .map(|title| fixture.note(title, "", &folder).unwrap())
+            .collect();
+        (fixture, ids)
+    }
+
+    #[test]
+    fn test_typing_filters_and_enter_picks() {
+        let (fixture, ids) = fixture();
+        let mut harness = Harness::new(fixture, false);
+        assert_eq!(
+            harness.titles(),
+            vec!["Groceries", "Meeting notes", "Garden plan"]
+        );
+
+        harness.type_text("meeting");
+        assert_eq!(harness.app.query, "meeting");
+        assert_eq!(harness.titles(), vec!["Meeting notes"]);
+
+        harness.press(egui::Key::Enter, egui::Modifiers::NONE);
+        assert_eq!(
+            harness.app.accepted,
+            Some(format!("[Meeting notes](:/{})", ids[1]))
+        );
+    }
+
+    #[test]
+    fn test_typing_keeps_selection() {
+        let (fixture, _) = fixture();
+        let mut harness = Harness::new(fixture, false);
+        harness.press(egui::Key::ArrowDown, egui::Modifiers::NONE);
+        assert_eq!(harness.previewed_title(), Some("Meeting notes"));
+
+        harness.type_text("-garden");
+        assert_eq!(harness.titles(), vec!["Groceries", "Meeting notes"]);
+        assert_eq!(harness.previewed_title(), Some("Meeting notes"));
+    }
+
+    #[test]
+    fn test_invalid_query_keeps_list() {
+        let (fixture, _) = fixture();
+        let mut harness = Harness::new(fixture, false);
+        harness.type_text("gar");
+        assert_eq!(harness.titles(), vec!["Garden plan"]);
+
+        harness.type_text(" is:maybe");
+        assert!(harness.app.query_error.is_some());
+        assert_eq!(harness.titles(), vec!["Garden plan"]);
+
+        for _ in "maybe".chars() {
+            harness.press(egui::Key::Backspace, egui::Modifiers::NONE);
+        }
+        harness.type_text("todo");
+        assert!(harness.app.query_error.is_none());
+        assert!(harness.titles().is_empty());
+    }
+
+    #[test]
+    fn test_keyboard_navigation() {
+        let (fixture, _) = fixture();
+        let mut harness = Harness::new(fixture, false);
+        assert_eq!(harness.previewed_title(), Some("Groceries"));
+
+        // Arrows work while typing in the query
+        harness.press(egui::Key::ArrowDown, egui::Modifiers::NONE);
+        assert_eq!(harness.previewed_title(), Some("Meeting notes"));
+
+        // Letters only move the selection in normal mode
+        harness.type_key(egui::Key::J, "j");
+        assert_eq!(harness.app.query, "j");
+        harness.press(egui::Key::Backspace, egui::Modifiers::NONE);
+        harness.press(egui::Key::Escape, egui::Modifiers::NONE);
+        harness.frame(Vec::new());
+        assert_eq!(Mode::from_focus(&harness.ctx, &[QUERY_ID]), Mode::Normal);
+
+        harness.type_key(egui::Key::G, "G");
+        assert_eq!(harness.previewed_title(), Some("Garden plan"));
+        harness.type_key(egui::Key::G, "g");
+        harness.type_key(egui::Key::G, "g");
+        assert_eq!(harness.previewed_title(), Some("Groceries"));
+        harness.type_key(egui::Key::J, "j");
+        assert_eq!(harness.previewed_title(), Some("Meeting notes"));
+        harness.type_key(egui::Key::K, "k");
+        harness.type_key(egui::Key::K, "k");
+        assert_eq!(harness.previewed_title(), Some("Groceries"));
+        assert_eq!(harness.app.query, "");
+
+        // `/` goes back to typing a query
+        harness.type_key(egui::Key::Slash, "/");
+        harness.type_text("garden");
+        assert_eq!(harness.app.query, "garden");
+        assert_eq!(harness.titles(), vec!["Garden plan"]);
+    }
+
+    #[test]
+    fn test_completing_todo_keeps_selection() {
+        let (fixture, ids) = fixture();
+        fixture
+            .execute(&format!(
+                "UPDATE notes SET is_todo = 1 WHERE id = '{}'",
+                ids[1]
+            ))
+            .unwrap();
+        let mut harness = Harness::new(fixture, true);
+        harness.press(egui::Key::Escape, egui::Modifiers::NONE);
+        harness.type_key(egui::Key::J, "j");
+        assert_eq!(harness.previewed_title(), Some("Meeting notes"));
+
+        harness.type_key(egui::Key::X, "x");
+        assert_eq!(harness.app.list.selected_item, Some(1));
+        assert!(harness.app.list.items[1].todo_completed.is_some());
+        let saved = Note::get_by_id(&harness.app.database, &ids[1]).unwrap();
+        assert!(saved.todo_completed.is_some());
+    }
+
+    #[test]
+    fn test_pinned_and_recent_notes() {
+        let (fixture, ids) = fixture();
+        let mut harness = Harness::new(fixture, false);
+        harness.app.options.history.record(&ids[0], 1).unwrap();
+        harness.app.update_quick_notes();
+        assert_eq!(harness.app.list.recent[0].title, "Groceries");
+
+        // Pin the last note from normal mode
+        harness.press(egui::Key::Escape, egui::Modifiers::NONE);
+        harness.type_key(egui::Key::G, "G");
+        harness.type_key(egui::Key::P, "p");
+        assert_eq!(harness.app.options.pins.ids(), [ids[2].clone()]);
+        assert_eq!(harness.app.list.quick_note(0).unwrap().title, "Garden plan");
+        assert_eq!(harness.app.list.quick_note(1).unwrap().title, "Groceries");
+
+        // Hidden while a query filters the list
+        harness.app.set_query("meeting");
+        assert!(harness.app.list.quick_note(0).is_none());
+        harness.app.set_query("");
+
+        harness.type_key(egui::Key::Num2, "2");
+        assert_eq!(
+            harness.app.accepted,
+            Some(format!("[Groceries](:/{})", ids[0]))
+        );
+    }
+
+    #[test]
+    fn test_session_is_restored() {
+        let (fixture, _) = fixture();
+        let mut harness = Harness::new(fixture, false);
+        harness.type_text("-meeting");
+        harness.app.sort = SortMode::Title;
+        harness.app.update_filtered_notes();
+        harness.press(egui::Key::ArrowDown, egui::Modifiers::NONE);
+        let session = harness.app.session();
+        let titles: Vec<String> = harness.titles().iter().map(|t| t.to_string()).collect();
+        let previewed = harness.previewed_title().map(str::to_string);
+        assert_eq!(session.query, "-meeting");
+        assert_eq!(titles, ["Garden plan", "Groceries"]);
+
+        harness.app.restore(Session::default());
+        assert_eq!(harness.app.query, "");
+        assert_eq!(harness.app.sort, SortMode::default());
+
+        harness.app.restore(session);
+        assert_eq!(harness.app.query, "-meeting");
+        assert_eq!(harness.app.sort, SortMode::Title);
+        assert_eq!(harness.titles(), titles);
+        assert_eq!(harness.previewed_title().map(str::to_string), previewed);
+
+        // A note that is gone leaves the first one selected
+        harness.app.restore(Session {
+            selected_id: Some("0".repeat(32)),
+            ..Session::default()
+        });
+        harness.frame(Vec::new());
+        assert_eq!(harness.app.list.selected_item, Some(0));
+    }
+
+    #[test]
+    fn test_prefilled_query_and_selection() {
+        let (fixture, ids) = fixture();
+        let mut harness = Harness::new(fixture, false);
+        assert_eq!(harness.app.skip_window(), None);
+
+        harness.app.restore(Session {
+            query: "-groceries".to_string(),
+            selected_id: Some(ids[2].clone()),
+            ..Session::default()
+        });
+        assert_eq!(harness.titles(), vec!["Meeting notes", "Garden plan"]);
+        assert_eq!(harness.previewed_title(), Some("Garden plan"));
+        assert_eq!(harness.app.skip_window(), None);
+
+        harness.app.set_query("garden");
+        assert_eq!(
+            harness.app.skip_window(),
+            Some(Skipped::Picked(format!("[Garden plan](:/{})", ids[2])))
+        );
+        harness.app.set_query("is:todo");
+        assert_eq!(harness.app.skip_window(), Some(Skipped::NoMatches));
+    }
+
+    #[test]
+    fn test_links_are_made_to_the_selected_note() {
+        let (fixture, ids) = fixture();
+        let mut harness = Harness::new(fixture, false);
+        let linked = Note::get_by_id(&harness.app.database, &ids[2]).unwrap();
+        harness.app.list.navigate_to(linked);
+        assert_eq!(harness.previewed_title(), Some("Garden plan"));
+
+        // Alt+← moves by word in the query box rather than going back
+        harness.press(egui::Key::ArrowLeft, egui::Modifiers::ALT);
+        assert_eq!(harness.previewed_title(), Some("Garden plan"));
+
+        harness.press(egui::Key::Enter, egui::Modifiers::NONE);
+        assert_eq!(
+            harness.app.accepted,
+            Some(format!("[Groceries](:/{})", ids[0]))
+        );
+
+        harness.press(egui::Key::Escape, egui::Modifiers::NONE);
+        harness.press(egui::Key::ArrowLeft, egui::Modifiers::ALT);
+        assert_eq!(harness.previewed_title(), Some("Groceries"));
+    }
+
+    #[test]
+    fn test_create_is_offered_without_an_exact_title() {
+        let (fixture, _) = fixture();
+        let mut harness = Harness::new(fixture, true);
+        assert_eq!(harness.app.new_title(), None);
+
+        harness.type_text("garden");
+        assert_eq!(harness.titles(), vec!["Garden plan"]);
+        assert_eq!(harness.app.new_title(), Some("garden".to_string()));
+
+        harness.type_text(" plan");
+        assert_eq!(harness.app.new_title(), None);
+    }
+
+    #[test]
+    fn test_sorting_loads_context_when_first_needed() {
+        let (mut fixture, ids) = fixture();
+        let folder = fixture.folder("Links", "").unwrap();
+        let body = format!("[notes](:/{})", ids[1]);
+        fixture.note("Hub", &body, &folder).unwrap();
+        let mut harness = Harness::new(fixture, false);
+
+        // A sort without context first, which must not leave an empty one behind
+        harness.app.sort = SortMode::Title;
+        harness.app.update_filtered_notes();
+        harness.app.sort = SortMode::Linked;
+        harness.app.update_filtered_notes();
+        assert_eq!(harness.titles()[0], "Meeting notes");
+    }
+}